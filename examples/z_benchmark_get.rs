@@ -17,8 +17,8 @@ extern crate fragmentation_e2e;
 use async_std::sync::Arc;
 use std::time::Instant;
 
-use fragmentation_e2e::{GETApiChunksArgs, GETApiFoldersArgs, ZenohCdn};
-use zenoh::{Properties, ZError};
+use fragmentation_e2e::{GETApiChunksArgs, GETApiFoldersArgs, ZenohCdn, ZenohCdnError};
+use zenoh::Properties;
 
 #[async_std::main]
 async fn main() {
@@ -37,7 +37,7 @@ async fn main() {
 
     let mut zenoh_cdn = ZenohCdn::new_session(config)
         .await
-        .map_err(|e: ZError| {
+        .map_err(|e: ZenohCdnError| {
             zenoh_util::zerror2!(zenoh::ZErrorKind::InvalidSession {
                 descr: format!("Error during creation of ZenohCdn: {}", e),
             })
@@ -16,8 +16,8 @@ extern crate fragmentation_e2e;
 
 use clap::{App, Arg};
 use core::default::Default;
-use fragmentation_e2e::{EVALApiArgs, ZenohCdn};
-use zenoh::{Properties, ZError};
+use fragmentation_e2e::{EVALApiArgs, ZenohCdn, ZenohCdnError};
+use zenoh::Properties;
 
 #[async_std::main]
 async fn main() {
@@ -28,7 +28,7 @@ async fn main() {
 
     let zenoh_cdn = ZenohCdn::new_session(config)
         .await
-        .map_err(|e: ZError| {
+        .map_err(|e: ZenohCdnError| {
             zenoh_util::zerror2!(zenoh::ZErrorKind::InvalidSession {
                 descr: format!("Error during creation of ZenohCdn: {}", e),
             })
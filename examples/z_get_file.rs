@@ -15,9 +15,15 @@
 extern crate fragmentation_e2e;
 
 use clap::{App, Arg};
-use fragmentation_e2e::{GETApiChunksArgs, GETApiFoldersArgs, ZenohCdn};
+use fragmentation_e2e::{
+    GETApiChunksArgs, GETApiFoldersArgs, TransferEvent, TransferEventKind, ZenohCdn, ZenohCdnError,
+};
+use futures::channel::mpsc::UnboundedReceiver;
+use futures::prelude::*;
+use std::io::Write;
+use std::path::PathBuf;
 use std::time::Instant;
-use zenoh::{Properties, ZError};
+use zenoh::Properties;
 
 #[async_std::main]
 async fn main() {
@@ -25,21 +31,22 @@ async fn main() {
     let (
         config,
         selector,
-        _root_folder,
+        root_folder,
         index_start,
         index_end,
         chunk_index_start,
         chunk_index_end,
+        max_in_flight,
     ) = parse_args();
 
-    let root_folder_final: &str = "/tmp/final";
-    let root_folder_chunks: &str = "/tmp/chunks";
+    let root_folder_final = PathBuf::from(root_folder);
+    let root_folder_chunks = PathBuf::from("/tmp/chunks");
 
     let start = Instant::now();
 
     let mut zenoh_cdn = ZenohCdn::new_session(config)
         .await
-        .map_err(|e: ZError| {
+        .map_err(|e: ZenohCdnError| {
             zenoh_util::zerror2!(zenoh::ZErrorKind::InvalidSession {
                 descr: format!("Error during creation of ZenohCdn: {}", e),
             })
@@ -58,13 +65,23 @@ async fn main() {
         index_end,
         chunk_index_start,
         chunk_index_end,
+        max_in_flight,
+        ..Default::default()
     });
 
+    async_std::task::spawn(show_progress(zenoh_cdn.transfer_events()));
+
     println!("Calling the GET API to retrieve the file...");
     let res: String = match zenoh_cdn.download(selector, "").await {
-        Ok(path) => format!(
-            "Finished to retrieve the file. The downloaded file is: {}",
-            path
+        Ok(report) => format!(
+            "Finished to retrieve the file. The downloaded file is: {} \
+             ({} bytes in {} chunks, {} retries, {:.0} bytes/s, {:?}).",
+            report.path.display(),
+            report.bytes_transferred,
+            report.chunks_fetched,
+            report.chunks_retried,
+            report.throughput(),
+            report.verification
         ),
         Err(e) => format!("Error during the Get: {:?}.", e),
     };
@@ -75,7 +92,59 @@ async fn main() {
     println!("{}", res);
 }
 
-fn parse_args() -> (Properties, String, String, usize, usize, usize, usize) {
+async fn show_progress(mut events: UnboundedReceiver<TransferEvent>) {
+    let start = Instant::now();
+    while let Some(event) = events.next().await {
+        match event.kind {
+            TransferEventKind::ManifestResolved(manifest) => println!(
+                "Downloading {}: {} bytes in {} chunks.",
+                event.key, manifest.size, manifest.chunks_number
+            ),
+            TransferEventKind::Progress {
+                bytes_done,
+                bytes_total,
+            } => {
+                let ratio = bytes_done as f64 / bytes_total.max(1) as f64;
+                let elapsed = start.elapsed().as_secs_f64();
+                let eta = if ratio > 0.0 {
+                    elapsed / ratio - elapsed
+                } else {
+                    0.0
+                };
+                let bar_len = (ratio * 40.0) as usize;
+                print!(
+                    "\r[{}{}] {:>3.0}% {}/{} bytes, ETA {:.1}s",
+                    "#".repeat(bar_len),
+                    ".".repeat(40 - bar_len),
+                    ratio * 100.0,
+                    bytes_done,
+                    bytes_total,
+                    eta
+                );
+                let _ = std::io::stdout().flush();
+            }
+            TransferEventKind::Retry {
+                chunk_num, retry, ..
+            } => println!("\nRetrying chunk {} (retry {}).", chunk_num, retry),
+            TransferEventKind::ChecksumVerified(ok) => {
+                println!("\nChecksum {}.", if ok { "OK" } else { "ERROR" })
+            }
+            _ => (),
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn parse_args() -> (
+    Properties,
+    String,
+    String,
+    usize,
+    usize,
+    usize,
+    usize,
+    usize,
+) {
     let args = App::new("zenoh get example")
         .arg(
             Arg::from_usage("-m, --mode=[MODE] 'The zenoh session mode.")
@@ -115,6 +184,10 @@ fn parse_args() -> (Properties, String, String, usize, usize, usize, usize) {
             "-d, --chunk_end    'Index of the last chunk of the file to retrieve.'",
             ).default_value("0")
         )
+        .arg(Arg::from_usage(
+            "-w, --max_in_flight    'Maximum number of chunks requested in parallel.'",
+            ).default_value("8")
+        )
         .get_matches();
 
     let mut config = Properties::default();
@@ -149,6 +222,11 @@ fn parse_args() -> (Properties, String, String, usize, usize, usize, usize) {
         .unwrap()
         .parse::<usize>()
         .unwrap();
+    let max_in_flight = args
+        .value_of("max_in_flight")
+        .unwrap()
+        .parse::<usize>()
+        .unwrap();
 
     (
         config,
@@ -158,5 +236,6 @@ fn parse_args() -> (Properties, String, String, usize, usize, usize, usize) {
         index_end,
         chunk_start,
         chunk_end,
+        max_in_flight,
     )
 }
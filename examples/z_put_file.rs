@@ -14,25 +14,30 @@
 
 extern crate fragmentation_e2e;
 
+use std::path::PathBuf;
 use std::time::Instant;
 
 use clap::{App, Arg};
 use fragmentation_e2e::PUTApiArgs;
-use fragmentation_e2e::ZenohCdn;
-use zenoh::{Properties, ZError};
+use fragmentation_e2e::{PUTApiStagingArgs, StagingMode};
+use fragmentation_e2e::{TransferEvent, TransferEventKind};
+use fragmentation_e2e::{ZenohCdn, ZenohCdnError};
+use futures::channel::mpsc::UnboundedReceiver;
+use futures::prelude::*;
+use zenoh::Properties;
 
 #[async_std::main]
 async fn main() {
     env_logger::init();
 
-    let (config, path, value, chunk_size) = parse_args();
+    let (config, path, value, chunk_size, upload_staging) = parse_args();
     println!("Calling the PUT API to share the file...");
 
     let start = Instant::now();
 
     let mut zenoh_cdn = ZenohCdn::new_session(config)
         .await
-        .map_err(|e: ZError| {
+        .map_err(|e: ZenohCdnError| {
             zenoh_util::zerror2!(zenoh::ZErrorKind::InvalidSession {
                 descr: format!("Error during creation of ZenohCdn: {}", e),
             })
@@ -40,22 +45,46 @@ async fn main() {
         .unwrap();
 
     zenoh_cdn.set_upload_args(PUTApiArgs { chunk_size });
+    zenoh_cdn.set_upload_staging(upload_staging);
 
     let cretion_time = start.elapsed().as_micros();
     println!("ZenohCDN creation: {}us", cretion_time);
 
-    let res: String = match zenoh_cdn.upload(path, value).await {
-        Ok(_) => String::from("Finished to share the file."),
-        Err(e) => format!("Error during the Put: {:?}.", e),
+    async_std::task::spawn(show_progress(zenoh_cdn.transfer_events()));
+
+    let seed = match zenoh_cdn.upload(path, value).await {
+        Ok(seed) => seed,
+        Err(e) => {
+            println!("Error during the Put: {:?}.", e);
+            return;
+        }
     };
 
     let finish = start.elapsed().as_micros();
     println!("End of PUT Api: {}us", finish);
 
-    println!("{}", res);
+    println!("Sharing the file {}. Press Ctrl-C to stop.", seed.key());
+    future::pending::<()>().await;
+}
+
+async fn show_progress(mut events: UnboundedReceiver<TransferEvent>) {
+    let mut served: usize = 0;
+    while let Some(event) = events.next().await {
+        match event.kind {
+            TransferEventKind::ManifestPublished(manifest) => println!(
+                "Sharing {}: {} bytes in {} chunks.",
+                event.key, manifest.size, manifest.chunks_number
+            ),
+            TransferEventKind::ChunkServed { chunk_num } => {
+                served += 1;
+                println!("Served chunk {} ({} chunks served).", chunk_num, served);
+            }
+            _ => (),
+        }
+    }
 }
 
-fn parse_args() -> (Properties, String, String, usize) {
+fn parse_args() -> (Properties, String, String, usize, PUTApiStagingArgs) {
     let args = App::new("zenoh put example")
         .arg(
             Arg::from_usage("-m, --mode=[MODE] 'The zenoh session mode.")
@@ -85,6 +114,15 @@ fn parse_args() -> (Properties, String, String, usize) {
             )
             .default_value("65000"),
         )
+        .arg(
+            Arg::from_usage(
+                "-t, --staging=[DIR]      'The folder where to copy the file to share.'",
+            )
+            .default_value("/tmp"),
+        )
+        .arg(Arg::from_usage(
+            "--in-place 'Serve the file from its location, without copying it.'",
+        ))
         .get_matches();
 
     let mut config = Properties::default();
@@ -102,5 +140,16 @@ fn parse_args() -> (Properties, String, String, usize) {
     let chunk_size_string: String = args.value_of("csize").unwrap().to_string();
     let chunk_size: usize = chunk_size_string.parse::<usize>().unwrap();
 
-    (config, path, value, chunk_size)
+    let staging_folder = PathBuf::from(args.value_of("staging").unwrap());
+    let staging_mode = if args.is_present("in-place") {
+        StagingMode::ServeInPlace
+    } else {
+        StagingMode::Copy
+    };
+    let upload_staging = PUTApiStagingArgs {
+        staging_folder,
+        staging_mode,
+    };
+
+    (config, path, value, chunk_size, upload_staging)
 }
@@ -0,0 +1,134 @@
+//
+// Copyright (c) 2017, 2020 ADLINK Technology Inc.
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ADLINK zenoh team, <zenoh@adlink-labs.tech>
+//
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt::Write;
+
+/// A compact set of chunk numbers, from 1 to `len`.
+///
+/// It is serialized as a string: the number of chunks, a colon and the
+/// bits in hexadecimal, e.g. `"10:ff03"`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChunkBitmap {
+    len: usize,
+    bits: Vec<u8>,
+}
+
+impl ChunkBitmap {
+    /// Creates an empty bitmap for `len` chunks.
+    pub fn new(len: usize) -> ChunkBitmap {
+        ChunkBitmap {
+            len,
+            bits: vec![0; (len + 7) / 8],
+        }
+    }
+
+    /// Creates a bitmap holding the chunks from `start` to `end`, inclusive.
+    pub fn with_range(len: usize, start: usize, end: usize) -> ChunkBitmap {
+        let mut bitmap = ChunkBitmap::new(len);
+        for chunk_num in start..=end {
+            bitmap.set(chunk_num);
+        }
+        bitmap
+    }
+
+    /// Returns the number of chunks the bitmap covers.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the bitmap covers no chunk.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns true if the chunk number `chunk_num` is in the set.
+    pub fn contains(&self, chunk_num: usize) -> bool {
+        if chunk_num == 0 || chunk_num > self.len {
+            return false;
+        }
+        let i = chunk_num - 1;
+        self.bits[i / 8] & (1 << (i % 8)) != 0
+    }
+
+    /// Adds the chunk number `chunk_num` to the set. Out of range numbers are ignored.
+    pub fn set(&mut self, chunk_num: usize) {
+        if chunk_num == 0 || chunk_num > self.len {
+            return;
+        }
+        let i = chunk_num - 1;
+        self.bits[i / 8] |= 1 << (i % 8);
+    }
+
+    /// Returns the number of chunks in the set.
+    pub fn count(&self) -> usize {
+        self.bits.iter().map(|b| b.count_ones() as usize).sum()
+    }
+
+    /// Returns true if every chunk is in the set.
+    pub fn is_full(&self) -> bool {
+        self.count() == self.len
+    }
+
+    /// Returns the chunk numbers in the set.
+    pub fn chunks(&self) -> Vec<usize> {
+        (1..=self.len).filter(|n| self.contains(*n)).collect()
+    }
+
+    /// Encodes the bitmap as `<len>:<hex bits>`.
+    pub fn encode(&self) -> String {
+        let mut encoded = format!("{}:", self.len);
+        for b in &self.bits {
+            let _ = write!(encoded, "{:02x}", b);
+        }
+        encoded
+    }
+
+    /// Decodes a bitmap encoded by `encode`.
+    pub fn decode(encoded: &str) -> Option<ChunkBitmap> {
+        let mut split = encoded.splitn(2, ':');
+        let len = split.next()?.parse::<usize>().ok()?;
+        let hex = split.next()?;
+        if hex.len() != (len + 7) / 8 * 2 || !hex.is_ascii() {
+            return None;
+        }
+        let bits = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+            .collect::<Option<Vec<u8>>>()?;
+        let bitmap = ChunkBitmap { len, bits };
+        // Bits past the last chunk must be zero, so that equal sets compare equal.
+        if (len + 1..=bitmap.bits.len() * 8).any(|n| {
+            let i = n - 1;
+            bitmap.bits[i / 8] & (1 << (i % 8)) != 0
+        }) {
+            return None;
+        }
+        Some(bitmap)
+    }
+}
+
+impl Serialize for ChunkBitmap {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.encode())
+    }
+}
+
+impl<'de> Deserialize<'de> for ChunkBitmap {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        ChunkBitmap::decode(&encoded)
+            .ok_or_else(|| serde::de::Error::custom(format!("invalid bitmap {}", encoded)))
+    }
+}
@@ -0,0 +1,354 @@
+//
+// Copyright (c) 2017, 2020 ADLINK Technology Inc.
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ADLINK zenoh team, <zenoh@adlink-labs.tech>
+//
+
+use crate::events::TransferListeners;
+use crate::{
+    ChunkScheduler, DownloadLimits, ExistingFilePolicy, GETApiChunksArgs, GETApiFoldersArgs,
+    HashAlgorithm, PUTApiArgs, PUTApiStagingArgs, RetryPolicy, StagingMode, ZenohCdn,
+    ZenohCdnError,
+};
+use log::{info, warn};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use zenoh::{Properties, Zenoh};
+
+/// Prefix of the environment variables overriding the configuration,
+/// e.g. `ZENOH_CDN_CHUNK_SIZE` or `ZENOH_CDN_ZENOH_MODE`.
+const ENV_PREFIX: &str = "ZENOH_CDN_";
+
+/// The configuration of a ZenohCDN, as read from a TOML or JSON5 file.
+///
+/// Every setting is optional: the missing ones keep their default value.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ZenohCdnConfig {
+    /// Properties of the zenoh session, e.g. `mode` or `peer`.
+    pub zenoh: BTreeMap<String, String>,
+    pub chunk_size: Option<usize>,
+    pub staging_folder: Option<PathBuf>,
+    pub staging_mode: Option<StagingMode>,
+    pub download_folder: Option<PathBuf>,
+    pub chunks_folder: Option<PathBuf>,
+    pub max_in_flight: Option<usize>,
+    pub strict_verification: Option<bool>,
+    pub existing_file: Option<ExistingFilePolicy>,
+    pub retry: RetryConfig,
+    pub limits: LimitsConfig,
+    pub hash_algorithm: Option<HashAlgorithm>,
+}
+
+/// The retry policy settings of the configuration, in milliseconds.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetryConfig {
+    pub max_retries: Option<usize>,
+    pub initial_backoff_ms: Option<u64>,
+    pub max_backoff_ms: Option<u64>,
+    pub query_timeout_ms: Option<u64>,
+    pub deadline_ms: Option<u64>,
+}
+
+/// The download limits settings of the configuration, sizes in bytes.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitsConfig {
+    pub max_file_size: Option<u64>,
+    pub max_chunks: Option<usize>,
+    pub max_chunk_size: Option<usize>,
+    pub check_free_space: Option<bool>,
+}
+
+impl ZenohCdnConfig {
+    /// Reads a configuration file, in JSON5 if its extension is `json` or `json5`, in TOML otherwise.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<ZenohCdnConfig, ZenohCdnError> {
+        let path = path.as_ref();
+        info!("Reading the configuration file {}.", path.display());
+        let content = fs::read_to_string(path)?;
+        let is_json5 = matches!(
+            path.extension().and_then(|ext| ext.to_str()),
+            Some("json") | Some("json5")
+        );
+        if is_json5 {
+            ZenohCdnConfig::from_json5(&content)
+        } else {
+            ZenohCdnConfig::from_toml(&content)
+        }
+    }
+
+    pub fn from_toml(content: &str) -> Result<ZenohCdnConfig, ZenohCdnError> {
+        toml::from_str(content).map_err(|e| ZenohCdnError::Config(format!("Invalid TOML: {}", e)))
+    }
+
+    pub fn from_json5(content: &str) -> Result<ZenohCdnConfig, ZenohCdnError> {
+        json5::from_str(content).map_err(|e| ZenohCdnError::Config(format!("Invalid JSON5: {}", e)))
+    }
+
+    /// Overrides the configuration with the `ZENOH_CDN_` variables of the environment.
+    pub fn apply_env(&mut self) -> Result<(), ZenohCdnError> {
+        self.apply_vars(std::env::vars())
+    }
+
+    /// Overrides the configuration with the `ZENOH_CDN_` variables among `vars`.
+    ///
+    /// `ZENOH_CDN_ZENOH_<NAME>` sets the zenoh session property `<name>`,
+    /// the other variables are named after the settings, e.g. `ZENOH_CDN_MAX_RETRIES`.
+    /// Unknown variables are ignored, but a known setting with an invalid value fails.
+    pub fn apply_vars(
+        &mut self,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<(), ZenohCdnError> {
+        for (name, value) in vars {
+            let setting = match name.strip_prefix(ENV_PREFIX) {
+                Some(setting) => setting.to_lowercase(),
+                None => continue,
+            };
+            if let Some(property) = setting.strip_prefix("zenoh_") {
+                self.zenoh.insert(property.to_string(), value);
+                continue;
+            }
+            match setting.as_str() {
+                "chunk_size" => self.chunk_size = Some(parse_var(&name, &value)?),
+                "staging_folder" => self.staging_folder = Some(PathBuf::from(value)),
+                "staging_mode" => self.staging_mode = Some(parse_var(&name, &value)?),
+                "download_folder" => self.download_folder = Some(PathBuf::from(value)),
+                "chunks_folder" => self.chunks_folder = Some(PathBuf::from(value)),
+                "max_in_flight" => self.max_in_flight = Some(parse_var(&name, &value)?),
+                "strict_verification" => self.strict_verification = Some(parse_var(&name, &value)?),
+                "existing_file" => self.existing_file = Some(parse_var(&name, &value)?),
+                "max_retries" => self.retry.max_retries = Some(parse_var(&name, &value)?),
+                "initial_backoff_ms" => {
+                    self.retry.initial_backoff_ms = Some(parse_var(&name, &value)?)
+                }
+                "max_backoff_ms" => self.retry.max_backoff_ms = Some(parse_var(&name, &value)?),
+                "query_timeout_ms" => self.retry.query_timeout_ms = Some(parse_var(&name, &value)?),
+                "deadline_ms" => self.retry.deadline_ms = Some(parse_var(&name, &value)?),
+                "max_file_size" => self.limits.max_file_size = Some(parse_var(&name, &value)?),
+                "max_chunks" => self.limits.max_chunks = Some(parse_var(&name, &value)?),
+                "max_chunk_size" => self.limits.max_chunk_size = Some(parse_var(&name, &value)?),
+                "check_free_space" => {
+                    self.limits.check_free_space = Some(parse_var(&name, &value)?)
+                }
+                "hash_algorithm" => self.hash_algorithm = Some(parse_var(&name, &value)?),
+                // Another tool may share the prefix: only the known settings must be valid.
+                _ => warn!("Unknown setting {}. Ignored.", name),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parses the value of a variable as a number, or else as a string, e.g. `serve_in_place`.
+fn parse_var<T: DeserializeOwned>(name: &str, value: &str) -> Result<T, ZenohCdnError> {
+    serde_json::from_str(value)
+        .or_else(|_| serde_json::from_value(serde_json::Value::String(value.to_string())))
+        .map_err(|e| ZenohCdnError::Config(format!("Invalid value {} for {}: {}", value, name, e)))
+}
+
+/// Builds a ZenohCDN, starting from the default configuration.
+#[derive(Clone, Default)]
+pub struct ZenohCdnBuilder {
+    zenoh_properties: BTreeMap<String, String>,
+    upload_args: PUTApiArgs,
+    upload_staging: PUTApiStagingArgs,
+    download_folders: GETApiFoldersArgs,
+    download_bytes_args: GETApiChunksArgs,
+    hash_algorithm: HashAlgorithm,
+}
+
+impl ZenohCdnBuilder {
+    pub fn new() -> ZenohCdnBuilder {
+        ZenohCdnBuilder::default()
+    }
+
+    /// Creates a builder from a configuration file, overridden by the environment.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<ZenohCdnBuilder, ZenohCdnError> {
+        let mut config = ZenohCdnConfig::from_file(path)?;
+        config.apply_env()?;
+        Ok(ZenohCdnBuilder::from_config(config))
+    }
+
+    /// Creates a builder from the default configuration, overridden by the environment.
+    pub fn from_env() -> Result<ZenohCdnBuilder, ZenohCdnError> {
+        let mut config = ZenohCdnConfig::default();
+        config.apply_env()?;
+        Ok(ZenohCdnBuilder::from_config(config))
+    }
+
+    pub fn from_config(config: ZenohCdnConfig) -> ZenohCdnBuilder {
+        let mut builder = ZenohCdnBuilder {
+            zenoh_properties: config.zenoh,
+            ..Default::default()
+        };
+        if let Some(chunk_size) = config.chunk_size {
+            builder.upload_args.chunk_size = chunk_size;
+        }
+        if let Some(staging_folder) = config.staging_folder {
+            builder.upload_staging.staging_folder = staging_folder;
+        }
+        if let Some(staging_mode) = config.staging_mode {
+            builder.upload_staging.staging_mode = staging_mode;
+        }
+        if let Some(download_folder) = config.download_folder {
+            builder.download_folders.root_folder_final = download_folder;
+        }
+        if let Some(chunks_folder) = config.chunks_folder {
+            builder.download_folders.root_folder_chunks = chunks_folder;
+        }
+        if let Some(max_in_flight) = config.max_in_flight {
+            builder.download_bytes_args.max_in_flight = max_in_flight;
+        }
+        if let Some(strict_verification) = config.strict_verification {
+            builder.download_bytes_args.strict_verification = strict_verification;
+        }
+        if let Some(existing_file) = config.existing_file {
+            builder.download_bytes_args.existing_file_policy = existing_file;
+        }
+        let retry_policy = &mut builder.download_bytes_args.retry_policy;
+        if let Some(max_retries) = config.retry.max_retries {
+            retry_policy.max_retries = max_retries;
+        }
+        if let Some(ms) = config.retry.initial_backoff_ms {
+            retry_policy.initial_backoff = Duration::from_millis(ms);
+        }
+        if let Some(ms) = config.retry.max_backoff_ms {
+            retry_policy.max_backoff = Duration::from_millis(ms);
+        }
+        if let Some(ms) = config.retry.query_timeout_ms {
+            retry_policy.query_timeout = Duration::from_millis(ms);
+        }
+        if let Some(ms) = config.retry.deadline_ms {
+            retry_policy.deadline = Some(Duration::from_millis(ms));
+        }
+        let limits = &mut builder.download_bytes_args.limits;
+        if let Some(max_file_size) = config.limits.max_file_size {
+            limits.max_file_size = max_file_size;
+        }
+        if let Some(max_chunks) = config.limits.max_chunks {
+            limits.max_chunks = max_chunks;
+        }
+        if let Some(max_chunk_size) = config.limits.max_chunk_size {
+            limits.max_chunk_size = max_chunk_size;
+        }
+        if let Some(check_free_space) = config.limits.check_free_space {
+            limits.check_free_space = check_free_space;
+        }
+        if let Some(hash_algorithm) = config.hash_algorithm {
+            builder.hash_algorithm = hash_algorithm;
+        }
+        builder
+    }
+
+    /// Sets a property of the zenoh session, e.g. `mode` or `peer`.
+    pub fn zenoh_property(mut self, key: &str, value: &str) -> ZenohCdnBuilder {
+        self.zenoh_properties
+            .insert(key.to_string(), value.to_string());
+        self
+    }
+
+    pub fn chunk_size(mut self, chunk_size: usize) -> ZenohCdnBuilder {
+        self.upload_args.chunk_size = chunk_size;
+        self
+    }
+
+    pub fn staging_folder(mut self, staging_folder: impl AsRef<Path>) -> ZenohCdnBuilder {
+        self.upload_staging.staging_folder = staging_folder.as_ref().to_path_buf();
+        self
+    }
+
+    pub fn staging_mode(mut self, staging_mode: StagingMode) -> ZenohCdnBuilder {
+        self.upload_staging.staging_mode = staging_mode;
+        self
+    }
+
+    pub fn download_folder(mut self, download_folder: impl AsRef<Path>) -> ZenohCdnBuilder {
+        self.download_folders.root_folder_final = download_folder.as_ref().to_path_buf();
+        self
+    }
+
+    pub fn chunks_folder(mut self, chunks_folder: impl AsRef<Path>) -> ZenohCdnBuilder {
+        self.download_folders.root_folder_chunks = chunks_folder.as_ref().to_path_buf();
+        self
+    }
+
+    pub fn max_in_flight(mut self, max_in_flight: usize) -> ZenohCdnBuilder {
+        self.download_bytes_args.max_in_flight = max_in_flight;
+        self
+    }
+
+    pub fn strict_verification(mut self, strict_verification: bool) -> ZenohCdnBuilder {
+        self.download_bytes_args.strict_verification = strict_verification;
+        self
+    }
+
+    pub fn existing_file_policy(mut self, policy: ExistingFilePolicy) -> ZenohCdnBuilder {
+        self.download_bytes_args.existing_file_policy = policy;
+        self
+    }
+
+    pub fn download_limits(mut self, limits: DownloadLimits) -> ZenohCdnBuilder {
+        self.download_bytes_args.limits = limits;
+        self
+    }
+
+    pub fn chunk_scheduler(mut self, scheduler: Arc<dyn ChunkScheduler>) -> ZenohCdnBuilder {
+        self.download_bytes_args.scheduler = scheduler;
+        self
+    }
+
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> ZenohCdnBuilder {
+        self.download_bytes_args.retry_policy = retry_policy;
+        self
+    }
+
+    pub fn hash_algorithm(mut self, hash_algorithm: HashAlgorithm) -> ZenohCdnBuilder {
+        self.hash_algorithm = hash_algorithm;
+        self
+    }
+
+    /// Returns the properties of the zenoh session to start.
+    pub fn zenoh_properties(&self) -> Properties {
+        let mut config = Properties::default();
+        for (key, value) in &self.zenoh_properties {
+            config.insert(key.clone(), value.clone());
+        }
+        config
+    }
+
+    /// Creates the ZenohCDN, starting a new Zenoh session.
+    pub async fn build(self) -> Result<ZenohCdn, ZenohCdnError> {
+        info!("New zenoh...");
+        let zenoh = Arc::new(Zenoh::new(self.zenoh_properties().into()).await?);
+        Ok(self.build_with_session(zenoh))
+    }
+
+    /// Creates the ZenohCDN from an existing Zenoh session, ignoring the zenoh properties.
+    pub fn build_with_session(self, zenoh: Arc<Zenoh>) -> ZenohCdn {
+        ZenohCdn {
+            zenoh,
+            upload_args: self.upload_args,
+            upload_staging: self.upload_staging,
+            download_folders: self.download_folders,
+            download_bytes_args: self.download_bytes_args,
+            hash_algorithm: self.hash_algorithm,
+            listeners: TransferListeners::default(),
+            download_seeds: Arc::new(Mutex::new(HashMap::new())),
+            seeder_id: format!("{:016x}", rand::random::<u64>()),
+        }
+    }
+}
@@ -0,0 +1,157 @@
+//
+// Copyright (c) 2017, 2020 ADLINK Technology Inc.
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ADLINK zenoh team, <zenoh@adlink-labs.tech>
+//
+
+use std::error::Error;
+use std::fmt;
+use std::path::PathBuf;
+use zenoh::ZError;
+
+/// The errors of the ZenohCDN APIs.
+#[derive(Debug)]
+pub enum ZenohCdnError {
+    /// An argument of the call is empty or out of its bounds.
+    InvalidArgument(String),
+    /// The bytes or chunks interval to download does not fit the file.
+    InvalidRange(String),
+    /// No file, nor manifest, is published on the key.
+    ManifestNotFound(String),
+    /// The manifest cannot be decoded, or its format version is not supported.
+    ManifestMalformed(String),
+    /// A value received from zenoh has not the expected encoding.
+    UnexpectedValue(String),
+    /// The downloaded file does not match the checksum of the manifest.
+    ChecksumMismatch {
+        path: String,
+    },
+    /// Chunks have not been received after all the retries.
+    /// `indexes` are the missing chunks, in ascending order.
+    ChunkMissing {
+        indexes: Vec<usize>,
+    },
+    /// The transfer has been cancelled through its handle.
+    Cancelled,
+    /// The manifest exceeds the limits of the download.
+    LimitExceeded(String),
+    /// The file system of `path` has not enough free space for the download.
+    InsufficientSpace {
+        path: PathBuf,
+        needed: u64,
+        available: u64,
+    },
+    /// The configuration cannot be read.
+    Config(String),
+    Io(std::io::Error),
+    Zenoh(ZError),
+}
+
+impl fmt::Display for ZenohCdnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ZenohCdnError::InvalidArgument(msg) => write!(f, "Invalid argument: {}", msg),
+            ZenohCdnError::InvalidRange(msg) => write!(f, "Invalid range: {}", msg),
+            ZenohCdnError::ManifestNotFound(key) => {
+                write!(f, "Metadata information not found for {}.", key)
+            }
+            ZenohCdnError::ManifestMalformed(msg) => write!(f, "Malformed manifest: {}", msg),
+            ZenohCdnError::UnexpectedValue(msg) => write!(f, "Unexpected value: {}", msg),
+            ZenohCdnError::ChecksumMismatch { path } => {
+                write!(f, "The checksum of {} does not match the manifest.", path)
+            }
+            ZenohCdnError::ChunkMissing { indexes } => {
+                let indexes: Vec<String> = indexes.iter().map(|i| i.to_string()).collect();
+                let noun = if indexes.len() == 1 {
+                    "Chunk"
+                } else {
+                    "Chunks"
+                };
+                write!(
+                    f,
+                    "{} {} missing after all the retries.",
+                    noun,
+                    indexes.join(", ")
+                )
+            }
+            ZenohCdnError::Cancelled => write!(f, "Transfer cancelled."),
+            ZenohCdnError::LimitExceeded(msg) => write!(f, "Limit exceeded: {}", msg),
+            ZenohCdnError::InsufficientSpace {
+                path,
+                needed,
+                available,
+            } => write!(
+                f,
+                "Not enough free space in {}: {} bytes needed, {} available.",
+                path.display(),
+                needed,
+                available
+            ),
+            ZenohCdnError::Config(msg) => write!(f, "Invalid configuration: {}", msg),
+            ZenohCdnError::Io(e) => write!(f, "I/O error: {}", e),
+            ZenohCdnError::Zenoh(e) => write!(f, "Zenoh error: {}", e),
+        }
+    }
+}
+
+impl Error for ZenohCdnError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ZenohCdnError::Io(e) => Some(e),
+            ZenohCdnError::Zenoh(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for ZenohCdnError {
+    fn from(e: std::io::Error) -> Self {
+        ZenohCdnError::Io(e)
+    }
+}
+
+impl From<ZError> for ZenohCdnError {
+    fn from(e: ZError) -> Self {
+        ZenohCdnError::Zenoh(e)
+    }
+}
+
+impl From<serde_json::Error> for ZenohCdnError {
+    fn from(e: serde_json::Error) -> Self {
+        ZenohCdnError::ManifestMalformed(e.to_string())
+    }
+}
+
+/// Used by the readers of remote files, which only report I/O errors.
+impl From<ZenohCdnError> for std::io::Error {
+    fn from(e: ZenohCdnError) -> Self {
+        use std::io::ErrorKind;
+        if let ZenohCdnError::Io(e) = e {
+            return e;
+        }
+        let kind = match &e {
+            ZenohCdnError::InvalidArgument(_)
+            | ZenohCdnError::InvalidRange(_)
+            | ZenohCdnError::Config(_) => ErrorKind::InvalidInput,
+            ZenohCdnError::ManifestNotFound(_) => ErrorKind::NotFound,
+            ZenohCdnError::ManifestMalformed(_)
+            | ZenohCdnError::UnexpectedValue(_)
+            | ZenohCdnError::ChecksumMismatch { .. }
+            | ZenohCdnError::LimitExceeded(_) => ErrorKind::InvalidData,
+            ZenohCdnError::ChunkMissing { .. } => ErrorKind::TimedOut,
+            ZenohCdnError::Cancelled => ErrorKind::Interrupted,
+            ZenohCdnError::Io(_)
+            | ZenohCdnError::InsufficientSpace { .. }
+            | ZenohCdnError::Zenoh(_) => ErrorKind::Other,
+        };
+        std::io::Error::new(kind, e)
+    }
+}
@@ -0,0 +1,88 @@
+//
+// Copyright (c) 2017, 2020 ADLINK Technology Inc.
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ADLINK zenoh team, <zenoh@adlink-labs.tech>
+//
+
+use crate::FileManifest;
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Something that happened during an upload or a download.
+#[derive(Clone, Debug)]
+pub struct TransferEvent {
+    /// The key of the shared file.
+    pub key: String,
+    pub kind: TransferEventKind,
+}
+
+#[derive(Clone, Debug)]
+pub enum TransferEventKind {
+    /// The manifest of the file has been published.
+    ManifestPublished(FileManifest),
+    /// The manifest of the file to download has been retrieved.
+    ManifestResolved(FileManifest),
+    /// The seeders found for the file to download, other than this instance.
+    SeedersDiscovered(Vec<String>),
+    /// A chunk is going to be requested, before its first attempt.
+    ChunkRequested { chunk_num: usize },
+    /// A chunk has been received and verified.
+    ChunkReceived { chunk_num: usize, size: usize },
+    /// A reply for a chunk did not match the chunk digest and was discarded.
+    ChunkRejected { chunk_num: usize },
+    /// A chunk is going to be requested again.
+    Retry {
+        chunk_num: usize,
+        retry: usize,
+        backoff: Duration,
+    },
+    /// Bytes written so far, out of the bytes to download.
+    Progress {
+        bytes_done: usize,
+        bytes_total: usize,
+    },
+    /// The checksum of the whole file has been verified.
+    ChecksumVerified(bool),
+    /// A chunk has been sent to a downloader.
+    ChunkServed { chunk_num: usize },
+    /// The transfer has finished, `path` being the local file.
+    Completed { path: String },
+    /// The transfer has stopped with an error.
+    Failed { reason: String },
+}
+
+/// The subscribers of the transfer events, shared by the clones of a ZenohCdn.
+#[derive(Clone, Default)]
+pub(crate) struct TransferListeners {
+    senders: Arc<Mutex<Vec<UnboundedSender<TransferEvent>>>>,
+}
+
+impl TransferListeners {
+    pub(crate) fn subscribe(&self) -> UnboundedReceiver<TransferEvent> {
+        let (sender, receiver) = unbounded();
+        self.senders.lock().unwrap().push(sender);
+        receiver
+    }
+
+    /// Sends an event to every subscriber, forgetting the dropped ones.
+    pub(crate) fn emit(&self, key: &str, kind: TransferEventKind) {
+        let mut senders = self.senders.lock().unwrap();
+        if senders.is_empty() {
+            return;
+        }
+        let event = TransferEvent {
+            key: key.to_string(),
+            kind,
+        };
+        senders.retain(|sender| sender.unbounded_send(event.clone()).is_ok());
+    }
+}
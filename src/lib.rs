@@ -12,25 +12,93 @@
 //   ADLINK zenoh team, <zenoh@adlink-labs.tech>
 //
 
+mod bitmap;
+mod config;
+mod error;
+mod events;
+mod manifest;
+mod remote;
+mod report;
+mod scheduler;
+mod seed;
+mod state;
+mod swarm;
+mod transfer;
 mod utils;
-use futures::future::{AbortHandle, Abortable};
+pub use bitmap::ChunkBitmap;
+pub use config::{LimitsConfig, RetryConfig, ZenohCdnBuilder, ZenohCdnConfig};
+pub use error::ZenohCdnError;
+use events::TransferListeners;
+pub use events::{TransferEvent, TransferEventKind};
+use futures::channel::mpsc::UnboundedReceiver;
+pub use manifest::{
+    chunk_digest, chunks_number, merkle_root, FileManifest, HashAlgorithm, ManifestVersion,
+    MANIFEST_VERSION_MAJOR, MANIFEST_VERSION_MINOR,
+};
+pub use remote::RemoteFile;
+pub use report::{TransferReport, Verification};
+pub use scheduler::{ChunkScheduler, Random, RarestFirst, Sequential};
+pub use seed::{ChunkRequest, SeedHandle};
+use seed::{SeedStats, StopSignal};
+pub use state::DownloadState;
+pub use swarm::{ChunkSource, InFlight, Swarm, MAX_SEEDER_FAILURES};
+pub use transfer::{CleanupPolicy, ExistingFilePolicy, TransferControl, TransferHandle};
 use utils::*;
+pub use utils::{finalize_file, get_chunk_from_mmap, map_shared_file};
 
-use futures::{prelude::*, select};
+use futures::{prelude::*, select, stream};
 use log::{error, info, warn};
+use memmap::Mmap;
 use net::ZBuf;
-use std::error::Error;
-use std::fs::copy;
-use std::io::ErrorKind;
+use rand::Rng;
+use serde::Deserialize;
+use std::collections::{BTreeSet, HashMap};
+use std::fs::create_dir_all;
+use std::time::{Duration, Instant};
 use std::{
     convert::{TryFrom, TryInto},
     str, u64,
 };
-use std::{fs, path::Path, sync::Arc};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
 use zenoh::*;
 
-const ROOT_FOLDER: &str = "/tmp";
 const MSG_FILE_RECEIVED: &str = "OK";
+const STATE_SAVE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A chunk received from a downloader, with the seeder that sent it.
+/// The source is the seeder id, or the id of the node that replied, taken from
+/// the timestamp of the reply, if the chunk was requested to any seeder.
+struct ReceivedChunk {
+    content: Vec<u8>,
+    source: String,
+}
+
+/// What the evals serving the chunks of a file use from the ZenohCDN.
+///
+/// The evals do not hold a clone of the ZenohCDN: it owns the seeds of the
+/// downloaded files, which would then keep their own evals running forever.
+#[derive(Clone)]
+struct SeedContext {
+    zenoh: Arc<Zenoh>,
+    listeners: TransferListeners,
+    seeder_id: String,
+    staging_folder: PathBuf,
+}
+
+/// What the requests for the chunks of a download share.
+struct ChunkFetch<'a, 'w> {
+    workspace: &'a Workspace<'w>,
+    key: &'a str,
+    manifest: &'a FileManifest,
+    retry_policy: &'a RetryPolicy,
+    deadline: Option<Instant>,
+    swarm: &'a Swarm,
+    control: &'a TransferControl,
+}
 
 #[derive(Clone, Copy)]
 pub struct PUTApiArgs {
@@ -43,12 +111,72 @@ pub struct GETApiChunksArgs {
     pub index_end: usize,
     pub chunk_index_start: usize,
     pub chunk_index_end: usize,
+    /// Maximum number of chunk requests pending at the same time.
+    pub max_in_flight: usize,
+    /// How failed or unanswered chunk requests are retried.
+    pub retry_policy: RetryPolicy,
+    /// What to do with the partial data if the download is cancelled.
+    pub cleanup_policy: CleanupPolicy,
+    /// What to do if the file already exists in the download folder.
+    pub existing_file_policy: ExistingFilePolicy,
+    /// The bounds of the files accepted for download.
+    pub limits: DownloadLimits,
+    /// Fail the download if the file does not match its checksum. Otherwise, the
+    /// default, the mismatch is only reported in the `TransferReport`.
+    pub strict_verification: bool,
+    /// The order in which the chunks are requested.
+    pub scheduler: Arc<dyn ChunkScheduler>,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    /// Number of times a chunk is requested again after the first attempt.
+    pub max_retries: usize,
+    /// Wait before the first retry, doubled at each following retry.
+    pub initial_backoff: Duration,
+    /// Upper bound of the wait between two retries.
+    pub max_backoff: Duration,
+    /// Maximum time to wait for the replies to a single chunk request.
+    pub query_timeout: Duration,
+    /// Maximum duration of the whole download, if any.
+    pub deadline: Option<Duration>,
+}
+
+/// The bounds a manifest must respect before anything is allocated for its download.
+#[derive(Clone, Copy, Debug)]
+pub struct DownloadLimits {
+    /// Maximum size of a downloaded file, in bytes.
+    pub max_file_size: u64,
+    /// Maximum number of chunks of a downloaded file.
+    pub max_chunks: usize,
+    /// Maximum size of a chunk, in bytes.
+    pub max_chunk_size: usize,
+    /// Check that the download folders have enough free space before downloading.
+    pub check_free_space: bool,
+}
+
+/// Where the files shared with the PUT API are served from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StagingMode {
+    /// Copy the file into the staging folder, and serve the copy.
+    Copy,
+    /// Serve the chunks directly from the original file, without any copy.
+    /// The file must not change while it is shared.
+    ServeInPlace,
+}
+
+#[derive(Clone, Debug)]
+pub struct PUTApiStagingArgs {
+    /// Folder of the copies of the shared files.
+    pub staging_folder: PathBuf,
+    pub staging_mode: StagingMode,
+}
+
+#[derive(Clone, Debug)]
 pub struct GETApiFoldersArgs {
-    pub root_folder_final: &'static str,
-    pub root_folder_chunks: &'static str,
+    pub root_folder_final: PathBuf,
+    pub root_folder_chunks: PathBuf,
 }
 pub struct EVALApiArgs {
     pub chunk_size: usize,
@@ -58,8 +186,13 @@ pub struct EVALApiArgs {
 pub struct ZenohCdn {
     pub zenoh: Arc<Zenoh>,
     upload_args: PUTApiArgs,
+    upload_staging: PUTApiStagingArgs,
     download_folders: GETApiFoldersArgs,
     download_bytes_args: GETApiChunksArgs,
+    hash_algorithm: HashAlgorithm,
+    listeners: TransferListeners,
+    download_seeds: Arc<Mutex<HashMap<String, SeedHandle>>>,
+    seeder_id: String,
 }
 
 impl Default for crate::PUTApiArgs {
@@ -75,6 +208,51 @@ impl Default for crate::GETApiChunksArgs {
             index_end: 0,
             chunk_index_start: 0,
             chunk_index_end: 0,
+            max_in_flight: 8,
+            retry_policy: RetryPolicy::default(),
+            cleanup_policy: CleanupPolicy::default(),
+            existing_file_policy: ExistingFilePolicy::default(),
+            strict_verification: false,
+            limits: DownloadLimits::default(),
+            scheduler: Arc::new(Sequential),
+        }
+    }
+}
+
+impl Default for crate::DownloadLimits {
+    fn default() -> Self {
+        Self {
+            max_file_size: 64 << 30,
+            max_chunks: 1 << 20,
+            max_chunk_size: 64 << 20,
+            check_free_space: true,
+        }
+    }
+}
+
+impl Default for crate::RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+            query_timeout: Duration::from_secs(10),
+            deadline: None,
+        }
+    }
+}
+
+impl Default for crate::StagingMode {
+    fn default() -> Self {
+        StagingMode::Copy
+    }
+}
+
+impl Default for crate::PUTApiStagingArgs {
+    fn default() -> Self {
+        Self {
+            staging_folder: PathBuf::from("/tmp"),
+            staging_mode: StagingMode::default(),
         }
     }
 }
@@ -82,8 +260,8 @@ impl Default for crate::GETApiChunksArgs {
 impl Default for crate::GETApiFoldersArgs {
     fn default() -> Self {
         Self {
-            root_folder_final: "/tmp/final",
-            root_folder_chunks: "/tmp/chunks",
+            root_folder_final: PathBuf::from("/tmp/final"),
+            root_folder_chunks: PathBuf::from("/tmp/chunks"),
         }
     }
 }
@@ -94,35 +272,77 @@ impl Default for crate::EVALApiArgs {
     }
 }
 
+impl RetryPolicy {
+    /// Returns the wait before the retry number `retry` (starting from 1).
+    ///
+    /// The wait doubles at each retry, up to `max_backoff`, and is randomly
+    /// picked in its upper half to spread the retries of parallel requests.
+    pub fn backoff(&self, retry: usize) -> Duration {
+        let exponent = retry.saturating_sub(1).min(31) as u32;
+        let backoff = self
+            .initial_backoff
+            .checked_mul(1 << exponent)
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff);
+        let half = backoff / 2;
+        let jitter_nanos = rand::thread_rng().gen_range(0..=half.as_nanos() as u64);
+        half + Duration::from_nanos(jitter_nanos)
+    }
+}
+
+impl DownloadLimits {
+    /// Checks a manifest received from the network against the limits,
+    /// before anything is allocated for its download.
+    pub fn check(&self, manifest: &FileManifest) -> Result<(), ZenohCdnError> {
+        let exceeded = |msg: String| {
+            error!("{}", msg);
+            Err(ZenohCdnError::LimitExceeded(msg))
+        };
+        if manifest.size as u64 > self.max_file_size {
+            return exceeded(format!(
+                "File size {} above the limit of {} bytes.",
+                manifest.size, self.max_file_size
+            ));
+        }
+        if manifest.chunks_number > self.max_chunks {
+            return exceeded(format!(
+                "{} chunks, above the limit of {}.",
+                manifest.chunks_number, self.max_chunks
+            ));
+        }
+        if manifest.chunk_size > self.max_chunk_size {
+            return exceeded(format!(
+                "Chunk size {} above the limit of {} bytes.",
+                manifest.chunk_size, self.max_chunk_size
+            ));
+        }
+        let capacity = (manifest.chunks_number as u64).saturating_mul(manifest.chunk_size as u64);
+        if manifest.chunk_size == 0 || capacity < manifest.size as u64 {
+            return Err(ZenohCdnError::ManifestMalformed(format!(
+                "{} chunks of {} bytes cannot hold {} bytes.",
+                manifest.chunks_number, manifest.chunk_size, manifest.size
+            )));
+        }
+        Ok(())
+    }
+}
+
 impl ZenohCdn {
     /// Creates a ZenohCDN object from an existing Zenoh session.
-    pub async fn new(zenoh: Arc<Zenoh>) -> ZResult<ZenohCdn> {
-        let upload_args = PUTApiArgs::default();
-        let download_folders = GETApiFoldersArgs::default();
-        let download_bytes_args = GETApiChunksArgs::default();
-
-        Ok(ZenohCdn {
-            zenoh,
-            upload_args,
-            download_folders,
-            download_bytes_args,
-        })
+    pub async fn new(zenoh: Arc<Zenoh>) -> Result<ZenohCdn, ZenohCdnError> {
+        Ok(ZenohCdnBuilder::new().build_with_session(zenoh))
     }
 
     /// Creates a ZenohCDN object, starting a new Zenoh session.
-    pub async fn new_session(config: Properties) -> ZResult<ZenohCdn> {
+    pub async fn new_session(config: Properties) -> Result<ZenohCdn, ZenohCdnError> {
         info!("New zenoh...");
         let zenoh = Arc::new(Zenoh::new(config.into()).await?);
-        let upload_args = PUTApiArgs::default();
-        let download_folders = GETApiFoldersArgs::default();
-        let download_bytes_args = GETApiChunksArgs::default();
-
-        Ok(ZenohCdn {
-            zenoh,
-            upload_args,
-            download_folders,
-            download_bytes_args,
-        })
+        Ok(ZenohCdnBuilder::new().build_with_session(zenoh))
+    }
+
+    /// Returns a builder of ZenohCDN, to configure it before starting the Zenoh session.
+    pub fn builder() -> ZenohCdnBuilder {
+        ZenohCdnBuilder::new()
     }
 
     /// Returns Zenoh from Zenoh_cdn.
@@ -141,25 +361,13 @@ impl ZenohCdn {
     }
 
     /// Set the zenoh cdn's folder where to download the final file to share.
-    pub fn set_download_file_folder(&mut self, download_folder_final: &'static str) {
-        let folders: &GETApiFoldersArgs = self.download_folders();
-        let root_folder_final = download_folder_final;
-        let root_folder_chunks = folders.root_folder_chunks;
-        self.set_download_folders(GETApiFoldersArgs {
-            root_folder_final,
-            root_folder_chunks,
-        });
+    pub fn set_download_file_folder(&mut self, download_folder_final: impl AsRef<Path>) {
+        self.download_folders.root_folder_final = download_folder_final.as_ref().to_path_buf();
     }
 
     /// Set the zenoh cdn's folder where to download the file chunks to share.
-    pub fn set_download_chunks_folder(&mut self, download_folder_chunks: &'static str) {
-        let folders: &GETApiFoldersArgs = self.download_folders();
-        let root_folder_final = folders.root_folder_final;
-        let root_folder_chunks = download_folder_chunks;
-        self.set_download_folders(GETApiFoldersArgs {
-            root_folder_final,
-            root_folder_chunks,
-        });
+    pub fn set_download_chunks_folder(&mut self, download_folder_chunks: impl AsRef<Path>) {
+        self.download_folders.root_folder_chunks = download_folder_chunks.as_ref().to_path_buf();
     }
 
     /// Get a reference to the zenoh cdn's download bytes args.
@@ -182,20 +390,63 @@ impl ZenohCdn {
         self.upload_args = upload_args;
     }
 
+    /// Get the hash algorithm of the files shared by the zenoh cdn.
+    pub fn hash_algorithm(&self) -> HashAlgorithm {
+        self.hash_algorithm
+    }
+
+    /// Set the hash algorithm of the files shared by the zenoh cdn.
+    pub fn set_hash_algorithm(&mut self, hash_algorithm: HashAlgorithm) {
+        self.hash_algorithm = hash_algorithm;
+    }
+
+    /// Get a reference to the zenoh cdn's upload staging args.
+    pub fn upload_staging(&self) -> &PUTApiStagingArgs {
+        &self.upload_staging
+    }
+
+    /// Set the zenoh cdn's upload staging args.
+    pub fn set_upload_staging(&mut self, upload_staging: PUTApiStagingArgs) {
+        self.upload_staging = upload_staging;
+    }
+
+    /// Set the zenoh cdn's folder where to copy the files to share.
+    pub fn set_staging_folder(&mut self, staging_folder: impl AsRef<Path>) {
+        self.upload_staging.staging_folder = staging_folder.as_ref().to_path_buf();
+    }
+
+    /// Set whether the files to share are copied or served in place.
+    pub fn set_staging_mode(&mut self, staging_mode: StagingMode) {
+        self.upload_staging.staging_mode = staging_mode;
+    }
+
+    /// Returns the path where the file shared on `key` is copied in the staging folder.
+    pub fn staging_path(&self, key: &str) -> PathBuf {
+        staged_path(&self.upload_staging.staging_folder, key)
+    }
+
+    /// Returns the id of this ZenohCDN among the seeders of the files it shares.
+    /// Downloaders request chunks to it under `<key>/seeders/<id>/<n>`.
+    pub fn seeder_id(&self) -> &str {
+        &self.seeder_id
+    }
+
+    /// Returns a stream of the events of every upload and download of this ZenohCDN.
+    pub fn transfer_events(&self) -> UnboundedReceiver<TransferEvent> {
+        self.listeners.subscribe()
+    }
+
     /// API to send a file in a client-server fashion.
-    pub async fn send(&self, path: String, value: String) -> Result<(), Box<dyn Error>> {
+    pub async fn send(&self, path: String, value: String) -> Result<(), ZenohCdnError> {
         let same_path = path.clone();
+        let value_path = value.clone();
         let chunk_size: usize = check_put_args(&path, &value, self.upload_args)?;
 
-        let (filename, chunks_number) = self.share_file(path, value, chunk_size).await?;
+        let (filename, manifest, source) = self.share_file(path, value, chunk_size).await?;
         let selector = filename.clone();
 
-        println!("Chunk number: {}", chunks_number);
-        let chunks_nums: Vec<usize> = (1..=chunks_number).collect();
-        let tasks_abort = Vec::with_capacity(chunks_nums.len());
-        let t = self
-            .start_evals(same_path, chunks_nums, chunk_size, tasks_abort)
-            .await;
+        println!("Chunk number: {}", manifest.chunks_number);
+        let seed = self.seed_file(same_path.clone(), &manifest, source).await;
 
         //notify that I've shared a file with the pub api
         let workspace = self.zenoh.workspace(None).await.unwrap();
@@ -232,55 +483,85 @@ impl ZenohCdn {
                                     },
                                 _ => {
                                         error!("Cannot read the data [StringUtf8 expected].");
-                                        return Err("Cannot read the data [StringUtf8 expected].".into())
+                                        return Err(ZenohCdnError::UnexpectedValue("Cannot read the data [StringUtf8 expected].".into()))
                                     },
                             };
                         },
                         _ => {
                                 error!("Cannot read the change.value.");
-                                return Err("Cannot read the change.value.".into())
+                                return Err(ZenohCdnError::UnexpectedValue("Cannot read the change.value.".into()))
                         },
                     };
                 }
             );
         }
         change_stream.close().await.unwrap();
+        self.listeners.emit(
+            &same_path,
+            TransferEventKind::Completed { path: value_path },
+        );
 
-        self.stop_evals(t).await;
+        seed.stop().await;
         Ok(())
     }
 
-    /// API to share a file.
-    pub async fn upload(&self, path: String, value: String) -> Result<(), Box<dyn Error>> {
+    /// API to share a file. The file is served until the returned handle is stopped or dropped:
+    /// the caller must keep the handle alive for as long as the file is to be served.
+    pub async fn upload(&self, path: String, value: String) -> Result<SeedHandle, ZenohCdnError> {
         let chunk_size: usize = check_put_args(&path, &value, self.upload_args)?;
-        let (_filename, chunks_number) = self.share_file(path.clone(), value, chunk_size).await?;
-        let chunks_nums: Vec<usize> = (1..=chunks_number).collect();
-        self.call_eval(path, chunks_nums, chunk_size).await;
-        Ok(())
+        let (_filename, manifest, source) =
+            self.share_file(path.clone(), value, chunk_size).await?;
+        Ok(self.seed_file(path, &manifest, source).await)
     }
 
     /// API to share a file, specifying the chunk size.
+    /// The file is served until the returned handle is stopped or dropped:
+    /// the caller must keep the handle alive for as long as the file is to be served.
     pub async fn upload_extended(
         &self,
         path: String,
         value: String,
         chunk_size: usize,
-    ) -> Result<(), Box<dyn Error>> {
-        let (_filename, chunks_number) = self.share_file(path.clone(), value, chunk_size).await?;
-        let chunks_nums: Vec<usize> = (1..=chunks_number).collect();
-        let tasks_abort = Vec::with_capacity(chunks_nums.len());
-        self.start_evals(path, chunks_nums, chunk_size, tasks_abort)
-            .await;
-        Ok(())
+    ) -> Result<SeedHandle, ZenohCdnError> {
+        let (_filename, manifest, source) =
+            self.share_file(path.clone(), value, chunk_size).await?;
+        Ok(self.seed_file(path, &manifest, source).await)
+    }
+
+    /// Takes the handle of the evals serving a downloaded file, to stop them.
+    ///
+    /// A downloaded file is served to the other nodes until its handle is taken
+    /// and stopped, `stop_download_seeds` is called, or the ZenohCDN and all its
+    /// clones are dropped, including the ones held by the open remote files and
+    /// the downloads running in the background.
+    pub fn take_download_seed(&self, key: &str) -> Option<SeedHandle> {
+        self.download_seeds.lock().unwrap().remove(key)
+    }
+
+    /// Stops serving every downloaded file, waiting until their evals are closed.
+    pub async fn stop_download_seeds(&self) {
+        let seeds: Vec<SeedHandle> = self
+            .download_seeds
+            .lock()
+            .unwrap()
+            .drain()
+            .map(|(_, seed)| seed)
+            .collect();
+        for seed in seeds {
+            seed.stop().await;
+        }
     }
 
     /// Base method to to share a file.
+    /// Returns the filename, the published manifest and the file to serve the chunks from.
+    ///
+    /// A file not larger than a chunk is published inline: its content is put at the key itself.
     async fn share_file(
         &self,
         path: String,
         value: String,
         chunk_size: usize,
-    ) -> Result<(String, usize), Box<dyn Error>> {
+    ) -> Result<(String, FileManifest, PathBuf), ZenohCdnError> {
         info!("New workspace...");
         let workspace = self.zenoh.workspace(None).await?;
 
@@ -292,98 +573,267 @@ impl ZenohCdn {
                 return Err(e.into());
             }
         };
-        let file_size = file_metadata.len() as usize;
-        let mut chunks_number: usize = 0;
         let path_split: Vec<_> = path.split('/').collect();
         let filename: String = path_split[path_split.len() - 1].to_string();
-        if file_size <= chunk_size {
-            info!("Put Data ('{}': '{}')...\n", path, value);
-            workspace.put(&path.try_into()?, value.into()).await?;
+        let mut source = PathBuf::from(&value);
+        let hash_algorithm = self.hash_algorithm;
+        let inline = file_metadata.len() as usize <= chunk_size;
+        // The manifest describes the bytes published or served, not the file
+        // as it may be when read again.
+        let (file_size, checksum, chunk_digests) = if inline {
+            let content = fs::read(&value)?;
+            let digest = chunk_digest(hash_algorithm, &content);
+            let file_size = content.len();
+            info!("Put Data ('{}': {} bytes)...\n", path, file_size);
+            workspace
+                .put(&path.clone().try_into()?, content.into())
+                .await?;
+            (file_size, digest.clone(), vec![digest])
         } else {
-            let source = value.clone();
-            let destination = format!("{}/{}", ROOT_FOLDER, filename);
-            match copy(source.clone(), destination.clone()) {
-                Ok(_) => info!("Copied file from {} to {}.", source, destination),
-                Err(e) => {
-                    info!("Cannot copy the file from {} to {}.", source, destination);
-                    return Err(e.into());
-                }
-            };
-            let file_type = file_metadata.file_type();
-            info!("File size: {}", file_size);
-            info!("File type: {:?}", file_type);
+            if self.upload_staging.staging_mode == StagingMode::Copy {
+                create_dir_all(&self.upload_staging.staging_folder)?;
+                let destination = self.staging_path(&path);
+                match copy_replacing(Path::new(&value), &destination) {
+                    Ok(_) => info!("Copied file from {} to {}.", value, destination.display()),
+                    Err(e) => {
+                        info!(
+                            "Cannot copy the file from {} to {}.",
+                            value,
+                            destination.display()
+                        );
+                        return Err(e.into());
+                    }
+                };
+                source = destination;
+            }
+            get_file_digests(&source, chunk_size, hash_algorithm)?
+        };
+        let file_type = manifest::describe_file_type(file_metadata.file_type());
+        info!("File size: {}", file_size);
+        info!("File type: {}", file_type);
+        info!("Checksum: {:?}", checksum);
 
-            let input = Path::new(&value);
-            let checksum = checksums::hash_file(input, checksums::Algorithm::SHA2256);
-            info!("Checksum: {:?}", checksum);
+        // An inline file, even empty, is published as a single chunk.
+        let chunks_number = if inline {
+            1
+        } else {
+            manifest::chunks_number(file_size, chunk_size)
+        };
+        info!("Chunks number: {}", chunks_number);
 
-            chunks_number = file_size / chunk_size + 1;
-            info!("Chunks number: {}", chunks_number);
+        let metadata_path: String = format!("{}/metadata", path);
+        let mut manifest = FileManifest::new(
+            file_size,
+            hash_algorithm,
+            checksum,
+            chunk_size,
+            chunks_number,
+            file_type,
+        );
+        manifest.inline = inline;
+        manifest.set_chunk_digests(chunk_digests);
+        info!("Merkle root: {}", manifest.merkle_root);
+        let metadata = manifest.to_json()?;
+        info!("Selector: {}", metadata_path);
+        info!("Size metadata: {}", metadata.len());
+        workspace
+            .put(&metadata_path.try_into()?, Value::Json(metadata))
+            .await?;
+        self.listeners.emit(
+            &path,
+            TransferEventKind::ManifestPublished(manifest.clone()),
+        );
+        Ok((filename, manifest, source))
+    }
 
-            let metadata_path: String = format!("{}/metadata", path);
-            let metadata: String = format!(
-                "size: {}, checksum: {}, chunks_number: {}, chunk_size: {}, file_type: {:?}",
-                file_size, checksum, chunks_number, chunk_size, file_type
-            );
-            info!("Selector: {}", metadata_path);
-            info!("Size metadata: {}", metadata.len());
-            workspace
-                .put(&metadata_path.try_into()?, metadata.into())
-                .await?;
+    /// Method to serve the chunks of a shared file, unless it is published inline.
+    async fn seed_file(
+        &self,
+        path: String,
+        manifest: &FileManifest,
+        source: PathBuf,
+    ) -> SeedHandle {
+        if manifest.inline {
+            // The whole file is in the value at the key: there is no chunk to serve.
+            return SeedHandle::new(path);
         }
-        Ok((filename, chunks_number))
+        let chunks_number = manifest.chunks_number;
+        let chunks = ChunkBitmap::with_range(chunks_number, 1, chunks_number);
+        self.start_evals(path, Some(source), chunks, manifest.chunk_size)
+            .await
     }
 
     /// The API to download a file.
+    /// An empty `download_folder_final` stands for the download folder of the ZenohCDN.
     pub async fn download(
         &self,
         selector: String,
-        download_folder_final: &'static str,
-    ) -> Result<String, Box<dyn Error>> {
-        let folders: &GETApiFoldersArgs = self.download_folders();
-        let root_folder_chunks = folders.root_folder_chunks;
-        let mut root_folder_final = folders.root_folder_final;
-        if !download_folder_final.is_empty() {
-            root_folder_final = download_folder_final;
-        }
-        self.retrieve_file(
-            selector,
-            root_folder_final,
-            root_folder_chunks,
-            self.download_bytes_args(),
-        )
-        .await
+        download_folder_final: impl AsRef<Path>,
+    ) -> Result<TransferReport, ZenohCdnError> {
+        let folders = self.resolve_download_folders(download_folder_final.as_ref());
+        let key = selector.clone();
+        let result = self
+            .retrieve_file(
+                selector,
+                &folders,
+                self.download_bytes_args(),
+                &TransferControl::default(),
+            )
+            .await;
+        self.emit_result(&key, &result);
+        result
     }
 
     /// The API to download a file, specifying the indexes
     pub async fn download_extended(
         &self,
         selector: String,
-        download_folder_final: &str,
+        download_folder_final: impl AsRef<Path>,
         indexes: Option<&GETApiChunksArgs>,
-    ) -> Result<String, Box<dyn Error + '_>> {
-        let folders: &GETApiFoldersArgs = self.download_folders();
-        let root_folder_chunks = folders.root_folder_chunks;
-        let mut root_folder_final = folders.root_folder_final;
-        if !download_folder_final.is_empty() {
-            root_folder_final = download_folder_final;
-        }
+    ) -> Result<TransferReport, ZenohCdnError> {
+        let folders = self.resolve_download_folders(download_folder_final.as_ref());
+        self.download_with_folders(selector, &folders, indexes)
+            .await
+    }
+
+    /// The API to download a file, specifying both the folder of the file
+    /// and the folder of the chunks, and optionally the indexes.
+    pub async fn download_with_folders(
+        &self,
+        selector: String,
+        folders: &GETApiFoldersArgs,
+        indexes: Option<&GETApiChunksArgs>,
+    ) -> Result<TransferReport, ZenohCdnError> {
         let i: &GETApiChunksArgs = match indexes {
             Some(element) => element,
             None => self.download_bytes_args(),
         };
-        self.retrieve_file(selector, root_folder_final, root_folder_chunks, i)
-            .await
+        let key = selector.clone();
+        let result = self
+            .retrieve_file(selector, folders, i, &TransferControl::default())
+            .await;
+        self.emit_result(&key, &result);
+        result
+    }
+
+    /// The API to download a file in the background, returning a handle
+    /// to pause, resume or cancel the download.
+    pub fn download_with_handle(
+        &self,
+        selector: String,
+        download_folder_final: impl AsRef<Path>,
+        indexes: Option<GETApiChunksArgs>,
+    ) -> TransferHandle {
+        let zenoh_cdn = self.clone();
+        let folders = self.resolve_download_folders(download_folder_final.as_ref());
+        let indexes = indexes.unwrap_or_else(|| self.download_bytes_args().clone());
+        let control = TransferControl::default();
+        let task_control = control.clone();
+        let task = async_std::task::spawn(async move {
+            let key = selector.clone();
+            let result = zenoh_cdn
+                .retrieve_file(selector, &folders, &indexes, &task_control)
+                .await;
+            zenoh_cdn.emit_result(&key, &result);
+            result
+        });
+        TransferHandle::new(control, task)
+    }
+
+    /// The API to read a file without downloading it to disk.
+    ///
+    /// The chunks are fetched as they are read, following the retry policy
+    /// and the limits of the download args.
+    pub async fn open(&self, selector: String) -> Result<RemoteFile, ZenohCdnError> {
+        self.open_remote(selector, true).await
+    }
+
+    /// The API to read `len` bytes of a file from `offset`, without downloading it to disk.
+    ///
+    /// Only the chunks covering the range are fetched, and exactly the requested
+    /// bytes are returned. The range must fit the file. The seeders of the file
+    /// are not looked for: the chunks are requested to any of them. To read
+    /// several ranges of a file, `open` it once and use `RemoteFile::read_at`,
+    /// which reuses its manifest, its seeders and its cached chunks.
+    pub async fn read_range(
+        &self,
+        selector: String,
+        offset: u64,
+        len: usize,
+    ) -> Result<Vec<u8>, ZenohCdnError> {
+        let mut file = self.open_remote(selector, false).await?;
+        file.read_at(offset, len).await
+    }
+
+    /// Base method to open a remote file, looking for its seeders if `discover` is true.
+    async fn open_remote(
+        &self,
+        selector: String,
+        discover: bool,
+    ) -> Result<RemoteFile, ZenohCdnError> {
+        check_get_args(selector.clone())?;
+        let workspace = self.zenoh.workspace(None).await?;
+        let retry_policy = &self.download_bytes_args.retry_policy;
+        let deadline = retry_policy.deadline.map(|d| Instant::now() + d);
+        let (manifest, _, _) = self
+            .resolve_manifest(&workspace, &selector, deadline)
+            .await?;
+        self.download_bytes_args.limits.check(&manifest)?;
+        self.listeners.emit(
+            &selector,
+            TransferEventKind::ManifestResolved(manifest.clone()),
+        );
+        let swarm = if !discover || manifest.inline || manifest.size == 0 {
+            Swarm::default()
+        } else {
+            let timeout = within_deadline(retry_policy.query_timeout, deadline);
+            let swarm = self
+                .discover_seeders(&workspace, &selector, manifest.chunks_number, timeout)
+                .await;
+            self.listeners.emit(
+                &selector,
+                TransferEventKind::SeedersDiscovered(swarm.seeders()),
+            );
+            swarm
+        };
+        Ok(RemoteFile::new(self.clone(), selector, manifest, swarm))
+    }
+
+    /// Method to get the download folders, overriding the folder of the file if not empty.
+    fn resolve_download_folders(&self, download_folder_final: &Path) -> GETApiFoldersArgs {
+        let mut folders = self.download_folders().clone();
+        if !download_folder_final.as_os_str().is_empty() {
+            folders.root_folder_final = download_folder_final.to_path_buf();
+        }
+        folders
+    }
+
+    /// Method to notify the listeners of the outcome of a download.
+    fn emit_result(&self, key: &str, result: &Result<TransferReport, ZenohCdnError>) {
+        let kind = match result {
+            Ok(report) => TransferEventKind::Completed {
+                path: report.path.to_string_lossy().into_owned(),
+            },
+            Err(e) => TransferEventKind::Failed {
+                reason: e.to_string(),
+            },
+        };
+        self.listeners.emit(key, kind);
     }
 
     /// Base method to retrieve a file.
     async fn retrieve_file(
         &self,
         selector: String,
-        root_folder_final: &str,
-        root_folder_chunks: &str,
+        folders: &GETApiFoldersArgs,
         indexes: &GETApiChunksArgs,
-    ) -> Result<String, Box<dyn Error>> {
+        control: &TransferControl,
+    ) -> Result<TransferReport, ZenohCdnError> {
+        let start = Instant::now();
+        let retry_policy = &indexes.retry_policy;
+        let deadline = retry_policy.deadline.map(|d| start + d);
+        let root_folder_final = folders.root_folder_final.as_path();
+        let root_folder_chunks = folders.root_folder_chunks.as_path();
         check_get_args(selector.clone())?;
 
         let index_start: usize = indexes.index_start;
@@ -395,165 +845,917 @@ impl ZenohCdn {
         let workspace = self.zenoh.workspace(None).await?;
 
         let old_selector = selector.clone();
-        info!("Get Data from {}'...\n", selector);
-        let mut data_stream = workspace.get(&selector.try_into()?).await?;
-
-        let mut found_selector = false;
-        while let Some(_data) = data_stream.next().await {
-            found_selector = true;
-        }
-        let mut path_to_return = "".to_string();
-        if !found_selector {
-            let metadata_selector = format!("{}/metadata", old_selector);
-            info!("Metadata selector: {}", metadata_selector);
-            let mut data_stream = workspace.get(&metadata_selector.try_into()?).await?;
-            let mut metadata: String = String::from("");
-            while let Some(data) = data_stream.next().await {
-                metadata = match data.value {
-                    Value::StringUtf8(s) => s,
-                    _ => {
-                        error!("Cannot read the data [StringUtf8 expected].");
-                        return Err("Cannot read the data [StringUtf8 expected].".into());
-                    }
-                };
+        let (manifest, filename, metadata) = self
+            .resolve_manifest(&workspace, &old_selector, deadline)
+            .await?;
+        indexes.limits.check(&manifest)?;
+        let size = manifest.size;
+        let chunks_number = manifest.chunks_number;
+        let chunk_size = manifest.chunk_size;
+        self.listeners.emit(
+            &old_selector,
+            TransferEventKind::ManifestResolved(manifest.clone()),
+        );
+        let path = root_folder_final
+            .join(&filename)
+            .to_string_lossy()
+            .into_owned();
+        if indexes.existing_file_policy == ExistingFilePolicy::SkipIfSame
+            && manifest.matches_file(Path::new(&path))
+        {
+            info!("{} is already downloaded, skipping it.", path);
+            if !manifest.inline && size > 0 {
+                let completed = ChunkBitmap::with_range(chunks_number, 1, chunks_number);
+                self.seed_download(&old_selector, &path, completed, chunk_size)
+                    .await;
             }
-
-            if metadata.is_empty() {
-                return Err(std::io::Error::new(
-                    ErrorKind::NotFound,
-                    "Metadata information not found.",
-                )
-                .into());
+            return Ok(TransferReport {
+                key: old_selector,
+                path: PathBuf::from(path),
+                bytes_transferred: 0,
+                chunks_fetched: 0,
+                chunks_retried: 0,
+                sources: BTreeSet::new(),
+                duration: start.elapsed(),
+                verification: Verification::Verified,
+            });
+        }
+        // An empty file has nothing to fetch, nor to map.
+        if manifest.inline || size == 0 {
+            if indexes.limits.check_free_space {
+                check_free_space(&[(root_folder_final, size as u64)])?;
             }
+            return self
+                .retrieve_inline(
+                    &workspace,
+                    &old_selector,
+                    &manifest,
+                    Path::new(&path),
+                    indexes,
+                    start,
+                )
+                .await;
+        }
+
+        let (chunk_start, chunk_end) = get_chunks_interval(
+            chunks_number,
+            chunk_size,
+            index_start,
+            index_end,
+            chunk_index_start,
+            chunk_index_end,
+        )?;
 
-            let (size, checksum, chunks_number, chunk_size, filename) =
-                get_metadata_info(metadata, old_selector.clone())?;
+        // The file is written under a temporary name until it is verified.
+        let partial = partial_path(Path::new(&path))
+            .to_string_lossy()
+            .into_owned();
+        let manifest_digest = chunk_digest(HashAlgorithm::default(), metadata.as_bytes());
+        let mut state =
+            DownloadState::load_or_new(Path::new(&path), &manifest_digest, chunks_number);
+        if manifest.chunk_len(chunks_number) == 0 {
+            // The empty last chunk of an older manifest has nothing to fetch.
+            state.completed.set(chunks_number);
+        }
 
-            let (chunk_start, chunk_end) = get_chunks_interval(
+        let chunks_to_fetch: Vec<usize> = (chunk_start..=chunk_end)
+            .filter(|chunk_num| !state.completed.contains(*chunk_num))
+            .collect();
+        let bytes_total: usize = (chunk_start..=chunk_end)
+            .map(|chunk_num| manifest.chunk_len(chunk_num))
+            .sum();
+        let bytes_to_fetch: usize = chunks_to_fetch
+            .iter()
+            .map(|chunk_num| manifest.chunk_len(*chunk_num))
+            .sum();
+        let mut bytes_done: usize = bytes_total - bytes_to_fetch;
+        // Every chunk is written both in the file and in its own chunk file.
+        if indexes.limits.check_free_space {
+            check_free_space(&[
+                (root_folder_final, bytes_to_fetch as u64),
+                (root_folder_chunks, bytes_to_fetch as u64),
+            ])?;
+        }
+        // The partial file of a previous download may still be served, and mapped:
+        // it must not be resized under its evals.
+        if let Some(seed) = self.take_download_seed(&old_selector) {
+            seed.stop().await;
+        }
+        let final_file = create_mmap_file(partial.clone(), root_folder_final, size as u64)?;
+        let mut final_mmap = map_mmap_file(&final_file)?;
+        info!(
+            "{} chunks to fetch, {} already downloaded.",
+            chunks_to_fetch.len(),
+            chunk_end - chunk_start + 1 - chunks_to_fetch.len()
+        );
+
+        let max_in_flight = indexes.max_in_flight.max(1);
+        info!("Fetching up to {} chunks in parallel.", max_in_flight);
+        let swarm = self
+            .discover_seeders(
+                &workspace,
+                &old_selector,
                 chunks_number,
+                within_deadline(retry_policy.query_timeout, deadline),
+            )
+            .await;
+        let seeders = swarm.seeders();
+        info!("{} seeders found for {}.", seeders.len(), old_selector);
+        self.listeners
+            .emit(&old_selector, TransferEventKind::SeedersDiscovered(seeders));
+        let fetch = &ChunkFetch {
+            workspace: &workspace,
+            key: &old_selector,
+            manifest: &manifest,
+            retry_policy,
+            deadline,
+            swarm: &swarm,
+            control,
+        };
+        let chunks_to_fetch = indexes.scheduler.schedule(chunks_to_fetch, &swarm);
+        let manifest = &manifest;
+        let chunk_selector = &old_selector;
+        let mut chunk_stream = stream::iter(chunks_to_fetch)
+            .map(|chunk_num| async move {
+                control.wait_while_paused().await;
+                if control.is_cancelled() {
+                    return (chunk_num, (None, 0));
+                }
+                let chunk_content = self.fetch_chunk(fetch, chunk_num).await;
+                (chunk_num, chunk_content)
+            })
+            .buffer_unordered(max_in_flight);
+        let mut missing_chunks: Vec<usize> = Vec::new();
+        let mut last_save = Instant::now();
+        let mut bytes_transferred: usize = 0;
+        let mut chunks_fetched: usize = 0;
+        let mut chunks_retried: usize = 0;
+        let mut sources: BTreeSet<String> = BTreeSet::new();
+        while let Some((chunk_num, (received, retries))) = chunk_stream.next().await {
+            chunks_retried += retries;
+            if control.is_cancelled() {
+                break;
+            }
+            let chunk_content = match received {
+                Some(received) => {
+                    sources.insert(received.source);
+                    received.content
+                }
+                None => {
+                    missing_chunks.push(chunk_num);
+                    continue;
+                }
+            };
+            bytes_transferred += chunk_content.len();
+            chunks_fetched += 1;
+            let filename_num = format!("{}_{}", &filename, chunk_num);
+            let full_filename = root_folder_chunks.join(filename_num);
+            write_mmap_file(&mut final_mmap, &chunk_content, chunk_num, chunk_size)?;
+            write_file(root_folder_chunks, chunk_content, full_filename)?;
+            state.completed.set(chunk_num);
+            bytes_done += manifest.chunk_len(chunk_num);
+            self.listeners.emit(
+                chunk_selector,
+                TransferEventKind::ChunkReceived {
+                    chunk_num,
+                    size: manifest.chunk_len(chunk_num),
+                },
+            );
+            self.listeners.emit(
+                chunk_selector,
+                TransferEventKind::Progress {
+                    bytes_done,
+                    bytes_total,
+                },
+            );
+            // The chunks must be on disk before the state file marks them as completed.
+            if last_save.elapsed() >= STATE_SAVE_INTERVAL {
+                final_mmap.flush()?;
+                state.save(Path::new(&path))?;
+                last_save = Instant::now();
+            }
+        }
+        drop(chunk_stream);
+        final_mmap.flush()?;
+        state.save(Path::new(&path))?;
+
+        if control.is_cancelled() {
+            warn!(
+                "Download of {} cancelled: {} of {} chunks downloaded.",
+                old_selector,
+                state.completed.count(),
+                chunks_number
+            );
+            if indexes.cleanup_policy == CleanupPolicy::RemovePartial {
+                drop(final_mmap);
+                drop(final_file);
+                remove_partial_download(
+                    Path::new(&path),
+                    root_folder_chunks,
+                    &filename,
+                    chunks_number,
+                )?;
+            }
+            return Err(ZenohCdnError::Cancelled);
+        }
+
+        if !missing_chunks.is_empty() {
+            missing_chunks.sort_unstable();
+            error!("Chunks {:?} missing after all the retries.", missing_chunks);
+            return Err(ZenohCdnError::ChunkMissing {
+                indexes: missing_chunks,
+            });
+        }
+
+        let count_chunks = state.completed.count();
+        let mut report_path = PathBuf::from(&partial);
+        let verification = if count_chunks == chunks_number {
+            let checksum_ok =
+                check_checksum(manifest.digest.clone(), manifest.hash_algorithm, &partial);
+            if !checksum_ok {
+                error!("Checksum verified -> ERROR. Please try to download the file again.");
+            } else {
+                info!("Checksum verified -> OK");
+            }
+            self.listeners.emit(
+                chunk_selector,
+                TransferEventKind::ChecksumVerified(checksum_ok),
+            );
+            // Either the file is complete or it must be downloaded again from scratch.
+            DownloadState::remove(Path::new(&path))?;
+            if checksum_ok {
+                drop(final_mmap);
+                report_path = finalize_file(
+                    &final_file,
+                    Path::new(&partial),
+                    Path::new(&path),
+                    indexes.existing_file_policy,
+                )?;
+                Verification::Verified
+            } else if indexes.strict_verification {
+                drop(final_mmap);
+                drop(final_file);
+                fs::remove_file(&partial)?;
+                return Err(ZenohCdnError::ChecksumMismatch { path });
+            } else {
+                // The corrupted file stays under its temporary name.
+                Verification::Mismatch
+            }
+        } else {
+            warn!(
+                "{} chunks missing. Check them to recreate the whole file.",
+                chunks_number - count_chunks
+            );
+            Verification::Partial
+        };
+        let report = TransferReport {
+            key: old_selector.clone(),
+            path: report_path,
+            bytes_transferred,
+            chunks_fetched,
+            chunks_retried,
+            sources,
+            duration: start.elapsed(),
+            verification,
+        };
+        info!(
+            "Downloaded {} bytes in {:?} ({:.0} bytes/s).",
+            report.bytes_transferred,
+            report.duration,
+            report.throughput()
+        );
+        // A corrupted file must not be served to the other downloaders.
+        if verification != Verification::Mismatch {
+            self.seed_download(&old_selector, &report.path, state.completed, chunk_size)
+                .await;
+        }
+        Ok(report)
+    }
+
+    /// Method to serve the downloaded chunks of a file to the other downloaders.
+    async fn seed_download(
+        &self,
+        key: &str,
+        path: impl AsRef<Path>,
+        completed: ChunkBitmap,
+        chunk_size: usize,
+    ) {
+        let seed = self
+            .start_evals(
+                key.to_string(),
+                Some(path.as_ref().to_path_buf()),
+                completed,
                 chunk_size,
-                index_start,
-                index_end,
-                chunk_index_start,
-                chunk_index_end,
-            )?;
-
-            let path = format!("{}/{}", root_folder_final, &filename);
-            path_to_return = path.clone();
-            let final_file = create_mmap_file(path.clone(), root_folder_final, size as u64)?;
-
-            for chunk_num in chunk_start..chunk_end + 1 {
-                let chunk_selector = format!("{}/{}", old_selector, chunk_num);
-                info!(
-                    "\nElaborating chunk number {}. Calling EVAL {}.",
-                    chunk_num, chunk_selector
-                );
-                let mut data_stream = workspace.get(&chunk_selector.try_into()?).await?;
-                while let Some(data) = data_stream.next().await {
-                    let chunk_content: ZBuf = match data.value.clone() {
-                        Value::Raw(_, buff) => buff,
-                        _ => {
-                            error!("Not the data expected [ZBuff required].");
-                            return Err("Not the data expected [ZBuff required].".into());
+            )
+            .await;
+        // Replacing a previous seed of the same file stops it.
+        self.download_seeds
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), seed);
+    }
+
+    /// Method to retrieve a file published inline, whose content is the value at its key,
+    /// or an empty file.
+    async fn retrieve_inline(
+        &self,
+        workspace: &Workspace<'_>,
+        selector: &str,
+        manifest: &FileManifest,
+        path: &Path,
+        indexes: &GETApiChunksArgs,
+        start: Instant,
+    ) -> Result<TransferReport, ZenohCdnError> {
+        let (content, sources) = if manifest.size == 0 {
+            (Vec::new(), BTreeSet::new())
+        } else {
+            self.query_inline(workspace, selector).await?
+        };
+        self.listeners.emit(
+            selector,
+            TransferEventKind::ChunkReceived {
+                chunk_num: 1,
+                size: content.len(),
+            },
+        );
+        self.listeners.emit(
+            selector,
+            TransferEventKind::Progress {
+                bytes_done: content.len(),
+                bytes_total: manifest.size,
+            },
+        );
+
+        let checksum_ok = content.len() == manifest.size
+            && chunk_digest(manifest.hash_algorithm, &content) == manifest.digest;
+        self.listeners
+            .emit(selector, TransferEventKind::ChecksumVerified(checksum_ok));
+        let verification = if checksum_ok {
+            info!("Checksum verified -> OK");
+            Verification::Verified
+        } else if indexes.strict_verification {
+            error!("Checksum verified -> ERROR. Please try to download the file again.");
+            return Err(ZenohCdnError::ChecksumMismatch {
+                path: path.to_string_lossy().into_owned(),
+            });
+        } else {
+            error!("Checksum verified -> ERROR. Please try to download the file again.");
+            Verification::Mismatch
+        };
+        if let Some(folder) = path.parent() {
+            create_dir_all(folder)?;
+        }
+        let partial = partial_path(path);
+        let mut file = fs::File::create(&partial)?;
+        std::io::Write::write_all(&mut file, &content)?;
+        // The corrupted file stays under its temporary name.
+        let report_path = if checksum_ok {
+            finalize_file(&file, &partial, path, indexes.existing_file_policy)?
+        } else {
+            partial
+        };
+        info!("Created file: {}", report_path.display());
+        Ok(TransferReport {
+            key: selector.to_string(),
+            path: report_path,
+            bytes_transferred: content.len(),
+            chunks_fetched: if manifest.size == 0 { 0 } else { 1 },
+            chunks_retried: 0,
+            sources,
+            duration: start.elapsed(),
+            verification,
+        })
+    }
+
+    /// Method to get the manifest of a file, with the file name and the manifest as published.
+    /// The replies received after `deadline` are ignored.
+    async fn resolve_manifest(
+        &self,
+        workspace: &Workspace<'_>,
+        selector: &str,
+        deadline: Option<Instant>,
+    ) -> Result<(FileManifest, String, String), ZenohCdnError> {
+        let metadata_selector = format!("{}/metadata", selector);
+        info!("Metadata selector: {}", metadata_selector);
+        let mut data_stream = workspace.get(&metadata_selector.try_into()?).await?;
+        let mut metadata: String = String::from("");
+        loop {
+            let next = data_stream.next();
+            let data = match deadline {
+                Some(deadline) => {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    match async_std::future::timeout(remaining, next).await {
+                        Ok(data) => data,
+                        Err(_) => {
+                            warn!("Download deadline reached while resolving {}.", selector);
+                            break;
                         }
-                    };
-                    let filename_num = format!("{}_{}", &filename, chunk_num);
-                    let full_filename = format!("{}/{}", root_folder_chunks, filename_num);
-                    write_mmap_file(&final_file, chunk_content.to_vec(), chunk_num, chunk_size);
-                    write_file(root_folder_chunks, chunk_content.to_vec(), full_filename)?;
+                    }
+                }
+                None => next.await,
+            };
+            let data = match data {
+                Some(data) => data,
+                None => break,
+            };
+            metadata = match data.value {
+                Value::Json(s) | Value::StringUtf8(s) => s,
+                _ => {
+                    error!("Cannot read the data [Json expected].");
+                    return Err(ZenohCdnError::UnexpectedValue(
+                        "Cannot read the data [Json expected].".into(),
+                    ));
                 }
+            };
+        }
+
+        if metadata.is_empty() {
+            return Err(ZenohCdnError::ManifestNotFound(selector.to_string()));
+        }
+
+        let (manifest, filename) = get_metadata_info(&metadata, selector.to_string())?;
+        Ok((manifest, filename, metadata))
+    }
+
+    /// Method to find the seeders of a file, other than this instance,
+    /// with the chunks each of them holds.
+    ///
+    /// Every seeder answers on `<key>/seeders/<id>` with its id, and on
+    /// `<key>/availability/<id>` with the bitmap of its chunks. The replies
+    /// received within `timeout` are kept. A seeder without a valid bitmap is
+    /// expected to hold every chunk.
+    async fn discover_seeders(
+        &self,
+        workspace: &Workspace<'_>,
+        key: &str,
+        chunks_number: usize,
+        timeout: Duration,
+    ) -> Swarm {
+        let (seeders, availability) = futures::join!(
+            self.query_all(workspace, format!("{}/seeders/*", key), timeout),
+            self.query_all(workspace, format!("{}/availability/*", key), timeout),
+        );
+        let swarm = Swarm::new(
+            seeders
+                .iter()
+                .filter_map(|data| self.seeder_of(data))
+                .collect::<Vec<String>>(),
+        );
+        for data in availability {
+            let id = match self.seeder_of(&data) {
+                Some(id) => id,
+                None => continue,
+            };
+            let chunks = match &data.value {
+                Value::StringUtf8(s) => ChunkBitmap::decode(s),
+                _ => None,
+            };
+            match chunks {
+                Some(chunks) if chunks.len() == chunks_number => {
+                    swarm.set_availability(&id, chunks)
+                }
+                _ => warn!("Invalid availability of the seeder {}. Ignored.", id),
             }
-            let count_chunks = chunk_end - chunk_start;
-            if count_chunks == chunks_number {
-                let checksum_ok = check_checksum(checksum, &path);
-                if !checksum_ok {
-                    error!("Checksum verified -> ERROR. Please try to download the file again.");
-                } else {
-                    info!("Checksum verified -> OK");
+        }
+        swarm
+    }
+
+    /// Returns the id of the seeder that sent a reply, the last segment of its path,
+    /// or `None` for the replies of this instance.
+    fn seeder_of(&self, data: &Data) -> Option<String> {
+        match data.path.as_str().rsplit('/').next() {
+            Some(id) if !id.is_empty() && id != self.seeder_id => Some(id.to_string()),
+            _ => None,
+        }
+    }
+
+    /// Method to send a request and return the replies received within `timeout`.
+    async fn query_all(
+        &self,
+        workspace: &Workspace<'_>,
+        selector: String,
+        timeout: Duration,
+    ) -> Vec<Data> {
+        let zselector: Selector = match selector.clone().try_into() {
+            Ok(zselector) => zselector,
+            Err(e) => {
+                warn!("Invalid selector {}: {}.", selector, e);
+                return Vec::new();
+            }
+        };
+        let mut data_stream = match workspace.get(&zselector).await {
+            Ok(data_stream) => data_stream,
+            Err(e) => {
+                warn!("Cannot request {}: {}.", selector, e);
+                return Vec::new();
+            }
+        };
+        let deadline = Instant::now() + timeout;
+        let mut replies: Vec<Data> = Vec::new();
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match async_std::future::timeout(remaining, data_stream.next()).await {
+                Ok(Some(data)) => replies.push(data),
+                Ok(None) | Err(_) => break,
+            }
+        }
+        replies
+    }
+
+    /// Method to get the value of an inline file, with the ids of the nodes it has been
+    /// received from.
+    async fn query_inline(
+        &self,
+        workspace: &Workspace<'_>,
+        selector: &str,
+    ) -> Result<(Vec<u8>, BTreeSet<String>), ZenohCdnError> {
+        let mut content: Option<Vec<u8>> = None;
+        let mut sources: BTreeSet<String> = BTreeSet::new();
+        info!("Get the inline data of {}.", selector);
+        let mut data_stream = workspace.get(&selector.to_string().try_into()?).await?;
+        while let Some(data) = data_stream.next().await {
+            match data.value {
+                Value::Raw(_, buff) => {
+                    content = Some(buff.to_vec());
+                    sources.insert(data.timestamp.get_id().to_string());
                 }
-            } else {
+                _ => warn!("Not the data expected from {} [ZBuff required].", data.path),
+            }
+        }
+        let content = content.ok_or(ZenohCdnError::ChunkMissing { indexes: vec![1] })?;
+        Ok((content, sources))
+    }
+
+    /// Method to fetch the chunk number `chunk_num` of a file in memory, checking its length.
+    /// The only chunk of an inline file is the whole file, checked against its checksum.
+    async fn read_chunk(
+        &self,
+        key: &str,
+        manifest: &FileManifest,
+        swarm: &Swarm,
+        chunk_num: usize,
+    ) -> Result<Vec<u8>, ZenohCdnError> {
+        let workspace = self.zenoh.workspace(None).await?;
+        let content = if manifest.inline {
+            let (content, _) = self.query_inline(&workspace, key).await?;
+            if chunk_digest(manifest.hash_algorithm, &content) != manifest.digest {
+                return Err(ZenohCdnError::ChecksumMismatch {
+                    path: key.to_string(),
+                });
+            }
+            content
+        } else {
+            let control = TransferControl::default();
+            let fetch = ChunkFetch {
+                workspace: &workspace,
+                key,
+                manifest,
+                retry_policy: &self.download_bytes_args.retry_policy,
+                deadline: None,
+                swarm,
+                control: &control,
+            };
+            match self.fetch_chunk(&fetch, chunk_num).await {
+                (Some(received), _) => received.content,
+                (None, _) => {
+                    return Err(ZenohCdnError::ChunkMissing {
+                        indexes: vec![chunk_num],
+                    })
+                }
+            }
+        };
+        let expected = manifest.chunk_len(chunk_num);
+        if content.len() != expected {
+            return Err(ZenohCdnError::UnexpectedValue(format!(
+                "Chunk {} of {} has {} bytes instead of {}.",
+                chunk_num,
+                key,
+                content.len(),
+                expected
+            )));
+        }
+        self.listeners.emit(
+            key,
+            TransferEventKind::ChunkReceived {
+                chunk_num,
+                size: expected,
+            },
+        );
+        Ok(content)
+    }
+
+    /// Method to retrieve a chunk, verifying it against the manifest.
+    ///
+    /// Each request goes to the seeder the swarm expects to answer first.
+    /// Replies that do not match the chunk digest are discarded. The chunk is
+    /// requested again, following the retry policy, to another seeder if any
+    /// is left, or else to any seeder of the file after a backoff.
+    /// Returns `None` if the retries are exhausted, the deadline is reached or
+    /// the download is cancelled, along with the number of retries. A cancellation
+    /// interrupts the request or the backoff in progress.
+    async fn fetch_chunk(
+        &self,
+        fetch: &ChunkFetch<'_, '_>,
+        chunk_num: usize,
+    ) -> (Option<ReceivedChunk>, usize) {
+        let retry_policy = fetch.retry_policy;
+        let mut tried: Vec<String> = Vec::new();
+        self.listeners
+            .emit(fetch.key, TransferEventKind::ChunkRequested { chunk_num });
+        for attempt in 0..=retry_policy.max_retries {
+            if fetch.control.is_cancelled() {
+                return (None, attempt);
+            }
+            // Every exit path drops the request, which stops counting as in flight.
+            let request = fetch.swarm.pick(chunk_num, &tried);
+            let source = request.source().clone();
+            if attempt > 0 {
+                // Switching to another seeder does not need to wait.
+                let backoff = match source {
+                    ChunkSource::Seeder(_) => Duration::from_secs(0),
+                    ChunkSource::Any => retry_policy.backoff(attempt),
+                };
                 warn!(
-                    "{} chunks missing. Check them to recreate the whole file.",
-                    count_chunks
+                    "Retrying chunk {} in {:?} (retry {} of {}).",
+                    chunk_num, backoff, attempt, retry_policy.max_retries
+                );
+                self.listeners.emit(
+                    fetch.key,
+                    TransferEventKind::Retry {
+                        chunk_num,
+                        retry: attempt,
+                        backoff,
+                    },
+                );
+                select!(
+                    _ = async_std::task::sleep(backoff).fuse() => (),
+                    _ = fetch.control.cancelled().fuse() => return (None, attempt),
                 );
             }
+            let mut query_timeout = retry_policy.query_timeout;
+            if let Some(deadline) = fetch.deadline {
+                let now = Instant::now();
+                if now >= deadline {
+                    error!("Download deadline reached before chunk {}.", chunk_num);
+                    return (None, attempt);
+                }
+                query_timeout = query_timeout.min(deadline - now);
+            }
+            info!(
+                "\nElaborating chunk number {} (attempt {}). Calling EVAL {}.",
+                chunk_num,
+                attempt + 1,
+                source.chunk_key(fetch.key, chunk_num)
+            );
+            let started = Instant::now();
+            let query = self.query_chunk(fetch, &source, chunk_num);
+            let reply = select!(
+                reply = async_std::future::timeout(query_timeout, query).fuse() => reply,
+                _ = fetch.control.cancelled().fuse() => return (None, attempt),
+            );
+            match reply {
+                Ok(Some(received)) => {
+                    request.succeeded(started.elapsed());
+                    return (Some(received), attempt);
+                }
+                Ok(None) => warn!("No valid reply for chunk {}.", chunk_num),
+                Err(_) => warn!(
+                    "Request for chunk {} timed out after {:?}.",
+                    chunk_num, query_timeout
+                ),
+            }
+            request.failed();
+            if let ChunkSource::Seeder(id) = source {
+                tried.push(id);
+            }
+        }
+        error!("No valid reply for chunk {}.", chunk_num);
+        (None, retry_policy.max_retries)
+    }
 
-            let chunks_nums: Vec<_> = (chunk_start..=chunk_end).collect();
-            let tasks_abort = Vec::with_capacity(chunks_nums.len());
-            self.start_evals(path.clone(), chunks_nums, chunk_size, tasks_abort)
-                .await;
+    /// Method to send a single request for a chunk and return the first valid reply.
+    async fn query_chunk(
+        &self,
+        fetch: &ChunkFetch<'_, '_>,
+        source: &ChunkSource,
+        chunk_num: usize,
+    ) -> Option<ReceivedChunk> {
+        let chunk_selector = source.chunk_key(fetch.key, chunk_num);
+        let zselector: Selector = match chunk_selector.clone().try_into() {
+            Ok(selector) => selector,
+            Err(e) => {
+                error!("Invalid chunk selector {}: {}.", chunk_selector, e);
+                return None;
+            }
+        };
+        let mut data_stream = match fetch.workspace.get(&zselector).await {
+            Ok(data_stream) => data_stream,
+            Err(e) => {
+                warn!("Cannot request chunk {}: {}.", chunk_num, e);
+                return None;
+            }
+        };
+        while let Some(data) = data_stream.next().await {
+            let chunk_content: ZBuf = match data.value {
+                Value::Raw(_, buff) => buff,
+                _ => {
+                    warn!("Not the data expected from {} [ZBuff required].", data.path);
+                    continue;
+                }
+            };
+            let chunk_content = chunk_content.to_vec();
+            let manifest = fetch.manifest;
+            if !manifest.has_chunk_digests() || manifest.verify_chunk(chunk_num, &chunk_content) {
+                let source = match source {
+                    ChunkSource::Seeder(id) => id.clone(),
+                    ChunkSource::Any => data.timestamp.get_id().to_string(),
+                };
+                return Some(ReceivedChunk {
+                    content: chunk_content,
+                    source,
+                });
+            }
+            warn!(
+                "Chunk {} from {} does not match its digest. Discarded.",
+                chunk_num, data.path
+            );
+            self.listeners
+                .emit(fetch.key, TransferEventKind::ChunkRejected { chunk_num });
         }
-        Ok(path_to_return)
+        None
     }
 
     /// Method to run multiple async evals forever.
     pub async fn call_eval(&self, path: String, chunks_nums: Vec<usize>, chunk_size: usize) {
-        let mut tasks = Vec::with_capacity(chunks_nums.len());
+        let mut chunks = ChunkBitmap::new(chunks_nums.iter().copied().max().unwrap_or(0));
         for n in chunks_nums {
-            let zenoh = self.clone();
-            let path_eval = path.to_string();
-            tasks.push(async_std::task::spawn(async move {
-                zenoh.eval(path_eval, n, chunk_size).await;
-            }));
+            chunks.set(n);
         }
-        for task in tasks {
-            task.await;
+        self.start_evals(path, None, chunks, chunk_size)
+            .await
+            .wait()
+            .await;
+    }
+
+    /// Method to get what the evals of the shared files need.
+    fn seed_context(&self) -> SeedContext {
+        SeedContext {
+            zenoh: self.zenoh.clone(),
+            listeners: self.listeners.clone(),
+            seeder_id: self.seeder_id.clone(),
+            staging_folder: self.upload_staging.staging_folder.clone(),
         }
     }
 
-    /// Method to run multiple, stoppable, async evals.
+    /// Method to run the stoppable evals serving the given chunks of a file.
+    /// Without a `source`, the chunks are served from the staging folder.
+    ///
+    /// The chunks are served both to any downloader, on `<path>/<n>`, and to the
+    /// downloaders asking this seeder, on `<path>/seeders/<id>/<n>`. Two more evals
+    /// answer on `<path>/seeders/<id>`, so that downloaders can find this seeder,
+    /// and on `<path>/availability/<id>` with the encoded bitmap of the chunks.
     async fn start_evals(
         &self,
         path: String,
-        chunks_nums: Vec<usize>,
+        source: Option<PathBuf>,
+        chunks: ChunkBitmap,
         chunk_size: usize,
-        mut tasks_abort: Vec<AbortHandle>,
-    ) -> Vec<AbortHandle> {
-        for n in chunks_nums.clone() {
-            let (handle, registration) = AbortHandle::new_pair();
-            tasks_abort.push(handle);
-
-            let zenoh = self.clone();
-            let path_eval = path.to_string();
-            let fut = async move {
-                zenoh.eval(path_eval, n, chunk_size).await;
-            };
-            async_std::task::spawn(Abortable::new(fut, registration));
+    ) -> SeedHandle {
+        let mut seed = SeedHandle::new(path.clone());
+        let context = self.seed_context();
+        let seeder_path = format!("{}/seeders/{}", path, self.seeder_id);
+        for eval_path in &[format!("{}/*", path), format!("{}/*", seeder_path)] {
+            let eval_path = eval_path.clone();
+            let context = context.clone();
+            let source = source.clone();
+            let chunks = chunks.clone();
+            let stop = seed.stop_signal();
+            let stats = seed.stats();
+            seed.push_task(async_std::task::spawn(async move {
+                context
+                    .eval(eval_path, source, chunks, chunk_size, stop, stats)
+                    .await;
+            }));
         }
-        tasks_abort
+        let announces = vec![
+            (
+                seeder_path,
+                Value::Json(serde_json::json!({ "id": self.seeder_id }).to_string()),
+            ),
+            (
+                format!("{}/availability/{}", path, self.seeder_id),
+                Value::StringUtf8(chunks.encode()),
+            ),
+        ];
+        for (eval_path, value) in announces {
+            let context = context.clone();
+            let stop = seed.stop_signal();
+            let stats = seed.stats();
+            seed.push_task(async_std::task::spawn(async move {
+                if let Err(e) = context.run_value_eval(eval_path, value, stop, stats).await {
+                    error!("Error during the Eval: {}.", e);
+                }
+            }));
+        }
+        seed
     }
 
-    /// Method to stop multiple async evals.
-    async fn stop_evals(&self, mut tasks_abort: Vec<AbortHandle>) -> Vec<AbortHandle> {
-        for handle in tasks_abort.drain(1..tasks_abort.len()) {
-            handle.abort();
-        }
-        tasks_abort
+    /// The API to retrieve bytes related the chunks
+    pub async fn run_eval_e2e(
+        &self,
+        path_str: String,
+        args: EVALApiArgs,
+    ) -> Result<(), ZenohCdnError> {
+        // The handle outlives the eval, which then runs forever.
+        let seed = SeedHandle::new(path_str.clone());
+        self.seed_context()
+            .run_eval(path_str, args, None, None, seed.stop_signal(), seed.stats())
+            .await
     }
+}
 
-    /// Method to call each evals.
-    async fn eval(&self, path: String, chunk_number: usize, chunk_size: usize) {
-        let eval_path = format!("{}/{}", path, chunk_number);
-        info!("Running Eval {} on path {}", chunk_number, eval_path);
-        let _ = match self
-            .run_eval_e2e(eval_path, EVALApiArgs { chunk_size })
+impl SeedContext {
+    /// Method to call the eval of the chunks of a file on `eval_path`.
+    async fn eval(
+        &self,
+        eval_path: String,
+        source: Option<PathBuf>,
+        chunks: ChunkBitmap,
+        chunk_size: usize,
+        stop: StopSignal,
+        stats: Arc<SeedStats>,
+    ) {
+        info!(
+            "Running Eval for {} chunks on path {}",
+            chunks.count(),
+            eval_path
+        );
+        match self
+            .run_eval(
+                eval_path.clone(),
+                EVALApiArgs { chunk_size },
+                source,
+                Some(chunks),
+                stop,
+                stats,
+            )
             .await
         {
-            Ok(_) => info!("Finished Eval {}", chunk_number),
+            Ok(_) => info!("Finished Eval on path {}", eval_path),
             Err(e) => error!("Error during the Eval: {}.", e),
         };
     }
 
-    /// The API to retrieve bytes related the chunks
-    pub async fn run_eval_e2e(
+    /// Method to answer every request on `eval_path` with `value`, until the stop signal.
+    async fn run_value_eval(
+        &self,
+        eval_path: String,
+        value: Value,
+        stop: StopSignal,
+        stats: Arc<SeedStats>,
+    ) -> Result<(), ZenohCdnError> {
+        let path = zenoh::Path::try_from(eval_path.clone())?;
+        let path_expr = PathExpr::try_from(eval_path.clone())?;
+        let workspace = self.zenoh.workspace(None).await?;
+        info!("Register eval for {}'...\n", eval_path);
+        let mut get_stream = workspace.register_eval(&path_expr).await?;
+        stats.eval_started();
+        loop {
+            let get_request = select!(
+                get_request = get_stream.next().fuse() => match get_request {
+                    Some(get_request) => get_request,
+                    None => break,
+                },
+                _ = stop.recv().fuse() => {
+                    info!("Stopping eval for {}.", eval_path);
+                    break;
+                },
+            );
+            get_request.reply(path.clone(), value.clone());
+        }
+        stats.eval_stopped();
+        get_stream.close().await?;
+        Ok(())
+    }
+
+    /// Base method to serve chunks until the stop signal.
+    ///
+    /// The chunk number is taken from the last segment of each selector,
+    /// so `path_str` may be a single chunk path or a wildcard like `<path>/*`.
+    /// The chunks are read from `source`, or else from the staged copy of the key.
+    /// If `chunks` is given, only those chunks are served.
+    async fn run_eval(
         &self,
         path_str: String,
         args: EVALApiArgs,
-    ) -> Result<(), Box<dyn Error>> {
+        source: Option<PathBuf>,
+        chunks: Option<ChunkBitmap>,
+        stop: StopSignal,
+        stats: Arc<SeedStats>,
+    ) -> Result<(), ZenohCdnError> {
         let _ = env_logger::try_init();
 
         let chunk_size: usize = check_eval_args(path_str.clone(), args)?;
-        let path: zenoh::Path = zenoh::Path::try_from(path_str.clone())?;
         let path_expr = PathExpr::try_from(path_str.clone())?;
 
         info!("New workspace...");
@@ -561,27 +1763,94 @@ impl ZenohCdn {
 
         info!("Register eval for {}'...\n", path_str);
         let mut get_stream = workspace.register_eval(&path_expr).await?;
-        while let Some(get_request) = get_stream.next().await {
+        // Each shared file is mapped once, on its first request.
+        let mut mapped_files: HashMap<PathBuf, Mmap> = HashMap::new();
+        stats.eval_started();
+        loop {
+            let get_request = select!(
+                get_request = get_stream.next().fuse() => match get_request {
+                    Some(get_request) => get_request,
+                    None => break,
+                },
+                _ = stop.recv().fuse() => {
+                    info!("Stopping eval for {}.", path_str);
+                    break;
+                },
+            );
             let selector = get_request.selector.clone();
             info!(
                 ">> [Eval listener] received get with selector: {}",
                 selector
             );
-            let selector_to_split = format!("{}", selector);
-            let selector_split: Vec<_> = selector_to_split.split('/').collect();
-            let filename = selector_split[selector_split.len() - 2];
-            let chunk_number = match selector_split[selector_split.len() - 1].parse::<usize>() {
-                Ok(chunk_number) => chunk_number,
+            let selector_path = selector.path_expr.to_string();
+            let request = match ChunkRequest::parse(&selector_path, &self.seeder_id) {
+                Some(request) => request,
+                None => {
+                    info!("Selector {} is not a chunk selector. Ignored.", selector);
+                    continue;
+                }
+            };
+            let (key, chunk_number) = (request.key, request.chunk_num);
+            if let Some(chunks) = &chunks {
+                if !chunks.contains(chunk_number) {
+                    info!("Chunk {} not available here. Ignored.", chunk_number);
+                    continue;
+                }
+            }
+            let path = match zenoh::Path::try_from(selector_path) {
+                Ok(path) => path,
                 Err(e) => {
-                    error!("Chunk number not a valid number: {}.", e);
-                    return Err(e.into());
+                    warn!("Cannot reply to {}: {}.", selector, e);
+                    continue;
                 }
             };
-
-            let chunk_bytes: Vec<u8> = get_bytes_from_file(filename, chunk_number, chunk_size);
-            info!(r#"Replying to GET "{:02X?}""#, &chunk_bytes[0..100]);
-            get_request.reply(path.clone(), chunk_bytes.into());
+            if let Err(e) = check_requested_key(&key) {
+                warn!("Request {} ignored: {}", selector, e);
+                continue;
+            }
+            let file = match &source {
+                Some(source) => source.clone(),
+                None => staged_path(&self.staging_folder, &key),
+            };
+            if !mapped_files.contains_key(&file) {
+                match map_shared_file(&file) {
+                    Ok(data) => {
+                        mapped_files.insert(file.clone(), data);
+                    }
+                    Err(e) => {
+                        error!("Cannot map the file {}: {}.", file.display(), e);
+                        continue;
+                    }
+                }
+            }
+            let chunk_bytes =
+                match get_chunk_from_mmap(&mapped_files[&file], chunk_number, chunk_size) {
+                    Ok(chunk_bytes) => chunk_bytes,
+                    Err(e) => {
+                        error!(
+                            "Cannot read the chunk {} of {}: {}.",
+                            chunk_number,
+                            file.display(),
+                            e
+                        );
+                        continue;
+                    }
+                };
+            info!(
+                r#"Replying to GET "{:02X?}""#,
+                &chunk_bytes[..chunk_bytes.len().min(100)]
+            );
+            // The reply owns its buffer, so the chunk is copied once from the map.
+            get_request.reply(path, chunk_bytes.to_vec().into());
+            stats.chunk_served(chunk_number);
+            self.listeners.emit(
+                &key,
+                TransferEventKind::ChunkServed {
+                    chunk_num: chunk_number,
+                },
+            );
         }
+        stats.eval_stopped();
         get_stream.close().await?;
         Ok(())
     }
@@ -0,0 +1,248 @@
+//
+// Copyright (c) 2017, 2020 ADLINK Technology Inc.
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ADLINK zenoh team, <zenoh@adlink-labs.tech>
+//
+
+use crate::ZenohCdnError;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::FileType;
+use std::path::Path;
+
+/// Major version of the manifest format written by this library.
+/// Readers reject manifests with a different major version.
+pub const MANIFEST_VERSION_MAJOR: u32 = 1;
+/// Minor version of the manifest format written by this library.
+/// Minor bumps only add optional fields, so they are always accepted.
+pub const MANIFEST_VERSION_MINOR: u32 = 3;
+
+/// The format version of a manifest.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestVersion {
+    pub major: u32,
+    pub minor: u32,
+}
+
+impl Default for ManifestVersion {
+    fn default() -> Self {
+        Self {
+            major: MANIFEST_VERSION_MAJOR,
+            minor: MANIFEST_VERSION_MINOR,
+        }
+    }
+}
+
+/// The algorithm used to compute the digests of a manifest.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HashAlgorithm {
+    #[serde(rename = "sha2-256")]
+    Sha2256,
+}
+
+impl Default for HashAlgorithm {
+    fn default() -> Self {
+        HashAlgorithm::Sha2256
+    }
+}
+
+impl HashAlgorithm {
+    /// Returns the matching algorithm of the checksums crate.
+    pub(crate) fn algorithm(self) -> checksums::Algorithm {
+        match self {
+            HashAlgorithm::Sha2256 => checksums::Algorithm::SHA2256,
+        }
+    }
+}
+
+/// The description of a shared file, published under `<path>/metadata`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileManifest {
+    pub version: ManifestVersion,
+    pub size: usize,
+    pub hash_algorithm: HashAlgorithm,
+    pub digest: String,
+    pub chunk_size: usize,
+    pub chunks_number: usize,
+    pub file_type: String,
+    #[serde(default)]
+    pub attributes: BTreeMap<String, String>,
+    /// Digest of every chunk, in chunk order (since 1.1).
+    #[serde(default)]
+    pub chunk_digests: Vec<String>,
+    /// Merkle root computed over `chunk_digests` (since 1.1).
+    #[serde(default)]
+    pub merkle_root: String,
+    /// The whole file is the value published at its key, instead of chunks (since 1.2).
+    #[serde(default)]
+    pub inline: bool,
+    /// Length of the last chunk (since 1.3).
+    #[serde(default)]
+    pub last_chunk_size: usize,
+}
+
+impl FileManifest {
+    /// Creates a manifest with the current format version and no attributes.
+    /// The length of the last chunk is computed from the size of the file.
+    pub fn new(
+        size: usize,
+        hash_algorithm: HashAlgorithm,
+        digest: String,
+        chunk_size: usize,
+        chunks_number: usize,
+        file_type: String,
+    ) -> FileManifest {
+        let mut manifest = FileManifest {
+            version: ManifestVersion::default(),
+            size,
+            hash_algorithm,
+            digest,
+            chunk_size,
+            chunks_number,
+            file_type,
+            attributes: BTreeMap::new(),
+            chunk_digests: Vec::new(),
+            merkle_root: String::new(),
+            inline: false,
+            last_chunk_size: 0,
+        };
+        manifest.last_chunk_size = manifest.chunk_len(chunks_number);
+        manifest
+    }
+
+    /// Returns the length of the chunk number `chunk_num` (starting from 1).
+    ///
+    /// Manifests older than 1.3 may advertise an extra chunk of length 0
+    /// after a file whose size is a multiple of the chunk size.
+    pub fn chunk_len(&self, chunk_num: usize) -> usize {
+        if chunk_num == 0 || chunk_num > self.chunks_number {
+            return 0;
+        }
+        let offset = (chunk_num - 1) * self.chunk_size;
+        self.size.saturating_sub(offset).min(self.chunk_size)
+    }
+
+    /// Sets the per-chunk digests and the Merkle root computed over them.
+    pub fn set_chunk_digests(&mut self, chunk_digests: Vec<String>) {
+        self.merkle_root = merkle_root(self.hash_algorithm, &chunk_digests);
+        self.chunk_digests = chunk_digests;
+    }
+
+    /// Returns true if the manifest carries per-chunk digests.
+    pub fn has_chunk_digests(&self) -> bool {
+        !self.chunk_digests.is_empty()
+    }
+
+    /// Checks that the chunk digests are consistent with the Merkle root
+    /// and cover every chunk of the file.
+    pub fn check_merkle_root(&self) -> bool {
+        self.chunk_digests.len() == self.chunks_number
+            && merkle_root(self.hash_algorithm, &self.chunk_digests) == self.merkle_root
+    }
+
+    /// Checks the bytes of the chunk number `chunk_num` (starting from 1)
+    /// against its digest.
+    pub fn verify_chunk(&self, chunk_num: usize, bytes: &[u8]) -> bool {
+        match chunk_num
+            .checked_sub(1)
+            .and_then(|i| self.chunk_digests.get(i))
+        {
+            Some(expected) => chunk_digest(self.hash_algorithm, bytes) == *expected,
+            None => false,
+        }
+    }
+
+    /// Checks the file at `path` against the checksum of the manifest.
+    /// A missing file does not match.
+    pub fn matches_file(&self, path: &Path) -> bool {
+        path.is_file() && checksums::hash_file(path, self.hash_algorithm.algorithm()) == self.digest
+    }
+
+    /// Encodes the manifest as JSON.
+    pub fn to_json(&self) -> Result<String, ZenohCdnError> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// Decodes a manifest from JSON, checking its format version first.
+    pub fn from_json(json: &str) -> Result<FileManifest, ZenohCdnError> {
+        let raw: serde_json::Value = serde_json::from_str(json)
+            .map_err(|e| ZenohCdnError::ManifestMalformed(format!("{}.", e)))?;
+        let major = raw
+            .get("version")
+            .and_then(|version| version.get("major"))
+            .and_then(|major| major.as_u64());
+        match major {
+            Some(major) if major == MANIFEST_VERSION_MAJOR as u64 => (),
+            Some(major) => {
+                return Err(ZenohCdnError::ManifestMalformed(format!(
+                    "Unsupported manifest version {} (supported major version: {}).",
+                    major, MANIFEST_VERSION_MAJOR
+                )))
+            }
+            None => {
+                return Err(ZenohCdnError::ManifestMalformed(
+                    "Missing format version.".into(),
+                ))
+            }
+        }
+        let manifest: FileManifest = serde_json::from_value(raw)
+            .map_err(|e| ZenohCdnError::ManifestMalformed(format!("{}.", e)))?;
+        Ok(manifest)
+    }
+}
+
+/// Returns the number of chunks of `chunk_size` bytes needed to hold `size` bytes.
+/// An empty file has no chunk.
+pub fn chunks_number(size: usize, chunk_size: usize) -> usize {
+    if chunk_size == 0 {
+        return 0;
+    }
+    (size + chunk_size - 1) / chunk_size
+}
+
+/// Returns a short, platform-independent description of a file type.
+pub(crate) fn describe_file_type(file_type: FileType) -> String {
+    if file_type.is_dir() {
+        String::from("dir")
+    } else if file_type.is_symlink() {
+        String::from("symlink")
+    } else {
+        String::from("file")
+    }
+}
+
+/// Computes the digest of a chunk.
+pub fn chunk_digest(algorithm: HashAlgorithm, bytes: &[u8]) -> String {
+    let mut reader = bytes;
+    checksums::hash_reader(&mut reader, algorithm.algorithm())
+}
+
+/// Computes the Merkle root of a list of chunk digests.
+///
+/// Each parent is the digest of the concatenation of its two children,
+/// and an odd node at the end of a level is promoted unchanged.
+pub fn merkle_root(algorithm: HashAlgorithm, digests: &[String]) -> String {
+    if digests.is_empty() {
+        return chunk_digest(algorithm, &[]);
+    }
+    let mut level: Vec<String> = digests.to_vec();
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| match pair {
+                [left, right] => chunk_digest(algorithm, format!("{}{}", left, right).as_bytes()),
+                [single] => single.clone(),
+                _ => unreachable!(),
+            })
+            .collect();
+    }
+    level.remove(0)
+}
@@ -0,0 +1,325 @@
+//
+// Copyright (c) 2017, 2020 ADLINK Technology Inc.
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ADLINK zenoh team, <zenoh@adlink-labs.tech>
+//
+
+use crate::{FileManifest, Swarm, ZenohCdn, ZenohCdnError};
+use futures::future::BoxFuture;
+use futures::io::{AsyncRead, AsyncSeek, SeekFrom};
+use futures::{stream, FutureExt, StreamExt, TryStreamExt};
+use log::warn;
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+/// Chunks fetched after the one being read, by default.
+const DEFAULT_READ_AHEAD: usize = 2;
+/// Chunks kept in memory, by default.
+const DEFAULT_CACHE_CHUNKS: usize = 16;
+
+/// The chunks kept in memory, evicting the least recently used one.
+struct ChunkCache {
+    capacity: usize,
+    chunks: HashMap<usize, Vec<u8>>,
+    /// The chunk numbers, from the least to the most recently used.
+    order: VecDeque<usize>,
+}
+
+impl ChunkCache {
+    fn new(capacity: usize) -> ChunkCache {
+        ChunkCache {
+            capacity,
+            chunks: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    fn contains(&self, chunk_num: usize) -> bool {
+        self.chunks.contains_key(&chunk_num)
+    }
+
+    fn get(&mut self, chunk_num: usize) -> Option<&[u8]> {
+        self.touch(chunk_num);
+        self.chunks.get(&chunk_num).map(Vec::as_slice)
+    }
+
+    fn insert(&mut self, chunk_num: usize, bytes: Vec<u8>) {
+        if self.chunks.insert(chunk_num, bytes).is_none() {
+            self.order.push_back(chunk_num);
+        }
+        self.shrink();
+    }
+
+    fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.shrink();
+    }
+
+    /// Marks a chunk as the most recently used one.
+    fn touch(&mut self, chunk_num: usize) {
+        if let Some(i) = self.order.iter().position(|n| *n == chunk_num) {
+            self.order.remove(i);
+            self.order.push_back(chunk_num);
+        }
+    }
+
+    fn shrink(&mut self) {
+        while self.chunks.len() > self.capacity {
+            match self.order.pop_front() {
+                Some(chunk_num) => {
+                    self.chunks.remove(&chunk_num);
+                }
+                None => break,
+            }
+        }
+    }
+}
+
+/// A shared file read through zenoh, returned by `ZenohCdn::open`.
+///
+/// The chunks covering each read are fetched on demand, along with the next
+/// chunks of the file, and the chunks read last are kept in memory. Each chunk
+/// is checked against its digest, if the manifest has them, but the file as a
+/// whole is never checked against its checksum.
+pub struct RemoteFile {
+    cdn: ZenohCdn,
+    key: String,
+    manifest: Arc<FileManifest>,
+    swarm: Arc<Swarm>,
+    position: u64,
+    read_ahead: usize,
+    cache: ChunkCache,
+    pending: HashMap<usize, BoxFuture<'static, Result<Vec<u8>, ZenohCdnError>>>,
+}
+
+impl RemoteFile {
+    pub(crate) fn new(
+        cdn: ZenohCdn,
+        key: String,
+        manifest: FileManifest,
+        swarm: Swarm,
+    ) -> RemoteFile {
+        RemoteFile {
+            cdn,
+            key,
+            manifest: Arc::new(manifest),
+            swarm: Arc::new(swarm),
+            position: 0,
+            read_ahead: DEFAULT_READ_AHEAD,
+            cache: ChunkCache::new(DEFAULT_CACHE_CHUNKS),
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Returns the key of the file.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Returns the manifest of the file.
+    pub fn manifest(&self) -> &FileManifest {
+        &self.manifest
+    }
+
+    /// Returns the size of the file, in bytes.
+    pub fn len(&self) -> u64 {
+        self.manifest.size as u64
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.manifest.size == 0
+    }
+
+    /// Returns the offset of the next read.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Sets the number of chunks fetched after the one being read.
+    /// The cache grows if it cannot hold them.
+    pub fn set_read_ahead(&mut self, read_ahead: usize) {
+        self.read_ahead = read_ahead;
+        let capacity = self.cache.capacity.max(read_ahead + 1);
+        self.cache.set_capacity(capacity);
+    }
+
+    /// Sets the number of chunks kept in memory. It is at least the chunk
+    /// being read and the chunks fetched after it.
+    pub fn set_cache_capacity(&mut self, chunks: usize) {
+        self.cache.set_capacity(chunks.max(self.read_ahead + 1));
+    }
+
+    /// Reads `len` bytes from `offset`, fetching only the chunks covering them
+    /// that are not cached, up to `max_in_flight` of the download args at the same
+    /// time. The chunks fetched are cached. The range must fit the file.
+    /// The position is left unchanged.
+    pub async fn read_at(&mut self, offset: u64, len: usize) -> Result<Vec<u8>, ZenohCdnError> {
+        let end = offset
+            .checked_add(len as u64)
+            .filter(|end| *end <= self.len())
+            .ok_or_else(|| {
+                ZenohCdnError::InvalidRange(format!(
+                    "{} bytes from {} do not fit the {} bytes of {}.",
+                    len,
+                    offset,
+                    self.len(),
+                    self.key
+                ))
+            })?;
+        if len == 0 {
+            return Ok(Vec::new());
+        }
+        let chunk_size = self.manifest.chunk_size as u64;
+        let first = (offset / chunk_size) as usize + 1;
+        let last = ((end - 1) / chunk_size) as usize + 1;
+        let max_in_flight = self.cdn.download_bytes_args().max_in_flight.max(1);
+        let missing: Vec<usize> = (first..=last)
+            .filter(|n| !self.cache.contains(*n))
+            .collect();
+        let (cdn, key, manifest, swarm) = (&self.cdn, &self.key, &self.manifest, &self.swarm);
+        let fetched: HashMap<usize, Vec<u8>> = stream::iter(missing)
+            .map(|n| async move {
+                let bytes = cdn.read_chunk(key, manifest, swarm, n).await?;
+                Ok::<_, ZenohCdnError>((n, bytes))
+            })
+            .buffered(max_in_flight)
+            .try_collect()
+            .await?;
+        let mut content = Vec::with_capacity(len);
+        for n in first..=last {
+            // The range may not fit the cache: the chunks fetched are cached once copied.
+            let bytes = match fetched.get(&n) {
+                Some(bytes) => bytes.as_slice(),
+                None => self.cache.get(n).unwrap_or_default(),
+            };
+            let chunk_start = (n as u64 - 1) * chunk_size;
+            let from = offset.saturating_sub(chunk_start) as usize;
+            let to = ((end - chunk_start) as usize).min(bytes.len());
+            content.extend_from_slice(&bytes[from..to]);
+        }
+        for (n, bytes) in fetched {
+            self.cache.insert(n, bytes);
+        }
+        Ok(content)
+    }
+
+    /// Requests the chunk number `chunk_num` and the chunks after it,
+    /// unless they are cached or already requested.
+    fn fetch_window(&mut self, chunk_num: usize) {
+        let last = (chunk_num + self.read_ahead).min(self.manifest.chunks_number);
+        // The requests left out of the window by a seek are dropped,
+        // which releases their seeders in the swarm.
+        self.pending.retain(|n, _| *n >= chunk_num && *n <= last);
+        for n in chunk_num..=last {
+            if self.cache.contains(n)
+                || self.pending.contains_key(&n)
+                || self.manifest.chunk_len(n) == 0
+            {
+                continue;
+            }
+            let cdn = self.cdn.clone();
+            let key = self.key.clone();
+            let manifest = self.manifest.clone();
+            let swarm = self.swarm.clone();
+            let request = async move { cdn.read_chunk(&key, &manifest, &swarm, n).await };
+            self.pending.insert(n, request.boxed());
+        }
+    }
+
+    /// Polls the pending requests, caching the chunks received.
+    /// Only the failure of the chunk number `needed` is returned:
+    /// the failed read-ahead chunks are requested again when they are read.
+    fn poll_pending(&mut self, cx: &mut Context<'_>, needed: usize) -> Result<(), ZenohCdnError> {
+        let mut done: Vec<(usize, Result<Vec<u8>, ZenohCdnError>)> = Vec::new();
+        for (n, request) in self.pending.iter_mut() {
+            if let Poll::Ready(result) = request.poll_unpin(cx) {
+                done.push((*n, result));
+            }
+        }
+        let mut failure = Ok(());
+        for (n, result) in done {
+            self.pending.remove(&n);
+            match result {
+                Ok(bytes) => self.cache.insert(n, bytes),
+                Err(e) if n == needed => failure = Err(e),
+                Err(e) => warn!("Cannot read ahead the chunk {} of {}: {}.", n, self.key, e),
+            }
+        }
+        failure
+    }
+}
+
+impl AsyncRead for RemoteFile {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if buf.is_empty() || this.position >= this.len() {
+            return Poll::Ready(Ok(0));
+        }
+        let chunk_size = this.manifest.chunk_size as u64;
+        let chunk_num = (this.position / chunk_size) as usize + 1;
+        let offset = (this.position % chunk_size) as usize;
+        // The chunk being read must not be evicted by the chunks read ahead.
+        this.cache.touch(chunk_num);
+        loop {
+            this.fetch_window(chunk_num);
+            if let Err(e) = this.poll_pending(cx, chunk_num) {
+                return Poll::Ready(Err(e.into()));
+            }
+            if let Some(chunk) = this.cache.get(chunk_num) {
+                let n = buf.len().min(chunk.len() - offset);
+                buf[..n].copy_from_slice(&chunk[offset..offset + n]);
+                this.position += n as u64;
+                return Poll::Ready(Ok(n));
+            }
+            if this.pending.contains_key(&chunk_num) {
+                return Poll::Pending;
+            }
+        }
+    }
+}
+
+impl AsyncSeek for RemoteFile {
+    /// Seeking past the end of the file is allowed: the reads then return no byte.
+    fn poll_seek(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        pos: SeekFrom,
+    ) -> Poll<io::Result<u64>> {
+        let this = self.get_mut();
+        let (base, offset) = match pos {
+            SeekFrom::Start(position) => (position, 0),
+            SeekFrom::End(offset) => (this.len(), offset),
+            SeekFrom::Current(offset) => (this.position, offset),
+        };
+        let position = if offset >= 0 {
+            base.checked_add(offset as u64)
+        } else {
+            base.checked_sub(offset.unsigned_abs())
+        };
+        match position {
+            Some(position) => {
+                this.position = position;
+                Poll::Ready(Ok(position))
+            }
+            None => Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Invalid seek to a negative or overflowing position.",
+            ))),
+        }
+    }
+}
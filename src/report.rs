@@ -0,0 +1,67 @@
+//
+// Copyright (c) 2017, 2020 ADLINK Technology Inc.
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ADLINK zenoh team, <zenoh@adlink-labs.tech>
+//
+
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// The outcome of the verification of a downloaded file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Verification {
+    /// The whole file matches the checksum of the manifest.
+    Verified,
+    /// The file does not match the checksum of the manifest.
+    Mismatch,
+    /// Only a part of the file has been downloaded, so its checksum is not verified.
+    /// Each chunk is still checked against its digest, if the manifest has them.
+    Partial,
+}
+
+/// The summary of a download, returned by the download APIs.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TransferReport {
+    /// The key of the downloaded file.
+    pub key: String,
+    /// The local path of the downloaded file.
+    pub path: PathBuf,
+    /// Bytes received in this download, without the ones resumed from a previous one.
+    pub bytes_transferred: usize,
+    /// Chunks received in this download.
+    pub chunks_fetched: usize,
+    /// Chunk requests sent again after a failed or unanswered one.
+    pub chunks_retried: usize,
+    /// The ids of the seeders the chunks have been received from. For the chunks
+    /// requested to any seeder and the inline files, the id of the node that
+    /// replied, taken from the timestamp of the reply.
+    pub sources: BTreeSet<String>,
+    pub duration: Duration,
+    pub verification: Verification,
+}
+
+impl TransferReport {
+    /// Returns the average throughput of the download, in bytes per second.
+    pub fn throughput(&self) -> f64 {
+        let seconds = self.duration.as_secs_f64();
+        if seconds > 0.0 {
+            self.bytes_transferred as f64 / seconds
+        } else {
+            0.0
+        }
+    }
+
+    /// Returns true if the whole file has been downloaded and matches its checksum.
+    pub fn is_verified(&self) -> bool {
+        self.verification == Verification::Verified
+    }
+}
@@ -0,0 +1,62 @@
+//
+// Copyright (c) 2017, 2020 ADLINK Technology Inc.
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ADLINK zenoh team, <zenoh@adlink-labs.tech>
+//
+
+use crate::Swarm;
+use rand::seq::SliceRandom;
+
+/// The order in which the chunks of a download are requested.
+///
+/// The chunks are scheduled once the seeders of the file are known,
+/// then requested in that order, up to `max_in_flight` at the same time.
+pub trait ChunkScheduler: Send + Sync {
+    /// Returns `chunks`, the chunk numbers still to fetch, in the order to request them.
+    fn schedule(&self, chunks: Vec<usize>, swarm: &Swarm) -> Vec<usize>;
+}
+
+/// Requests the chunks in ascending order, so that the file is written from its
+/// start. It suits progressive playback and streaming.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Sequential;
+
+impl ChunkScheduler for Sequential {
+    fn schedule(&self, mut chunks: Vec<usize>, _swarm: &Swarm) -> Vec<usize> {
+        chunks.sort_unstable();
+        chunks
+    }
+}
+
+/// Requests first the chunks held by the fewest seeders, so that they spread
+/// before their holders leave the swarm. Chunks held by as many seeders are
+/// requested in ascending order.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RarestFirst;
+
+impl ChunkScheduler for RarestFirst {
+    fn schedule(&self, mut chunks: Vec<usize>, swarm: &Swarm) -> Vec<usize> {
+        chunks.sort_by_cached_key(|chunk_num| (swarm.holders(*chunk_num), *chunk_num));
+        chunks
+    }
+}
+
+/// Requests the chunks in a random order, so that concurrent downloaders
+/// spread their requests over the whole file.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Random;
+
+impl ChunkScheduler for Random {
+    fn schedule(&self, mut chunks: Vec<usize>, _swarm: &Swarm) -> Vec<usize> {
+        chunks.shuffle(&mut rand::thread_rng());
+        chunks
+    }
+}
@@ -0,0 +1,161 @@
+//
+// Copyright (c) 2017, 2020 ADLINK Technology Inc.
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ADLINK zenoh team, <zenoh@adlink-labs.tech>
+//
+
+use async_std::channel::{bounded, Receiver, Sender};
+use async_std::task::JoinHandle;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// The requests served for a shared file, per chunk number.
+#[derive(Debug, Default)]
+pub(crate) struct SeedStats {
+    active_evals: AtomicUsize,
+    chunk_requests: Mutex<BTreeMap<usize, u64>>,
+}
+
+impl SeedStats {
+    pub(crate) fn eval_started(&self) {
+        self.active_evals.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub(crate) fn eval_stopped(&self) {
+        self.active_evals.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    pub(crate) fn chunk_served(&self, chunk_num: usize) {
+        *self
+            .chunk_requests
+            .lock()
+            .unwrap()
+            .entry(chunk_num)
+            .or_insert(0) += 1;
+    }
+}
+
+/// A request for a chunk of a shared file, as received by its evals.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChunkRequest {
+    /// The key of the shared file.
+    pub key: String,
+    pub chunk_num: usize,
+}
+
+impl ChunkRequest {
+    /// Parses the selector of a request, `<key>/<n>` or `<key>/seeders/<seeder_id>/<n>`.
+    ///
+    /// Returns `None` if the last segment is not a chunk number, like the
+    /// `<key>/metadata` or `<key>/*` requests reaching the same evals.
+    /// The chunk numbers start at 1.
+    pub fn parse(selector: &str, seeder_id: &str) -> Option<ChunkRequest> {
+        let i = selector.rfind('/')?;
+        let chunk_num = match selector[i + 1..].parse::<usize>() {
+            Ok(chunk_num) if chunk_num > 0 => chunk_num,
+            _ => return None,
+        };
+        let mut key = &selector[..i];
+        // The chunks asked to this seeder are the chunks of the same key.
+        let seeder_suffix = format!("/seeders/{}", seeder_id);
+        if key.ends_with(&seeder_suffix) {
+            key = &key[..key.len() - seeder_suffix.len()];
+        }
+        Some(ChunkRequest {
+            key: key.to_string(),
+            chunk_num,
+        })
+    }
+}
+
+/// The signal telling the evals of a shared file to stop.
+/// Every receiver wakes up once the sender is closed or dropped.
+pub(crate) type StopSignal = Receiver<()>;
+
+/// The evals serving a shared file, returned by the publishing APIs.
+///
+/// Dropping the handle stops the evals, closing their eval registrations.
+#[must_use = "dropping the handle stops seeding"]
+pub struct SeedHandle {
+    key: String,
+    stop_sender: Sender<()>,
+    stop_receiver: StopSignal,
+    tasks: Vec<JoinHandle<()>>,
+    stats: Arc<SeedStats>,
+}
+
+impl SeedHandle {
+    pub(crate) fn new(key: String) -> SeedHandle {
+        let (stop_sender, stop_receiver) = bounded(1);
+        SeedHandle {
+            key,
+            stop_sender,
+            stop_receiver,
+            tasks: Vec::new(),
+            stats: Arc::new(SeedStats::default()),
+        }
+    }
+
+    /// Returns the signal to pass to a new eval of the shared file.
+    pub(crate) fn stop_signal(&self) -> StopSignal {
+        self.stop_receiver.clone()
+    }
+
+    pub(crate) fn stats(&self) -> Arc<SeedStats> {
+        self.stats.clone()
+    }
+
+    pub(crate) fn push_task(&mut self, task: JoinHandle<()>) {
+        self.tasks.push(task);
+    }
+
+    /// Returns the key of the shared file.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Returns true while at least one eval is serving the file.
+    pub fn is_active(&self) -> bool {
+        !self.stop_sender.is_closed() && self.stats.active_evals.load(Ordering::SeqCst) > 0
+    }
+
+    /// Returns the number of requests served for each chunk number.
+    pub fn chunk_requests(&self) -> BTreeMap<usize, u64> {
+        self.stats.chunk_requests.lock().unwrap().clone()
+    }
+
+    /// Returns the number of requests served for the whole file.
+    pub fn requests_served(&self) -> u64 {
+        self.stats.chunk_requests.lock().unwrap().values().sum()
+    }
+
+    /// Waits until every eval of the file has ended, without stopping them.
+    pub(crate) async fn wait(mut self) {
+        for task in self.tasks.drain(..) {
+            task.await;
+        }
+    }
+
+    /// Stops every eval of the file and waits until their registrations are closed.
+    pub async fn stop(mut self) {
+        self.stop_sender.close();
+        for task in self.tasks.drain(..) {
+            task.await;
+        }
+    }
+}
+
+impl Drop for SeedHandle {
+    fn drop(&mut self) {
+        self.stop_sender.close();
+    }
+}
@@ -0,0 +1,100 @@
+//
+// Copyright (c) 2017, 2020 ADLINK Technology Inc.
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ADLINK zenoh team, <zenoh@adlink-labs.tech>
+//
+
+use crate::utils::partial_path;
+use crate::{ChunkBitmap, ZenohCdnError};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The progress of a download, persisted next to the downloaded file
+/// so that an interrupted download fetches only the missing chunks.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DownloadState {
+    /// Digest of the manifest the chunks were downloaded with.
+    pub manifest_digest: String,
+    /// Chunks already written to the downloaded file.
+    pub completed: ChunkBitmap,
+}
+
+impl DownloadState {
+    /// Creates the state of a download that has not started yet.
+    pub fn new(manifest_digest: String, chunks_number: usize) -> DownloadState {
+        DownloadState {
+            manifest_digest,
+            completed: ChunkBitmap::new(chunks_number),
+        }
+    }
+
+    /// Returns the path of the state file of a downloaded file: a hidden file next to it,
+    /// named after the final file rather than after its partial one.
+    pub fn path_for(file: &Path) -> PathBuf {
+        let filename = file
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        file.with_file_name(format!(".{}.state", filename))
+    }
+
+    /// Loads the state of a download, to resume it.
+    ///
+    /// Returns a fresh state if there is nothing to resume: no state file, an
+    /// unreadable one, a missing partial file, or a manifest that has changed.
+    pub fn load_or_new(file: &Path, manifest_digest: &str, chunks_number: usize) -> DownloadState {
+        let state_path = DownloadState::path_for(file);
+        if !partial_path(file).exists() {
+            return DownloadState::new(manifest_digest.to_string(), chunks_number);
+        }
+        let state: DownloadState = match fs::read_to_string(&state_path)
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+        {
+            Some(state) => state,
+            None => return DownloadState::new(manifest_digest.to_string(), chunks_number),
+        };
+        if state.manifest_digest != manifest_digest || state.completed.len() != chunks_number {
+            warn!(
+                "The remote manifest of {} has changed. Restarting the download.",
+                file.display()
+            );
+            return DownloadState::new(manifest_digest.to_string(), chunks_number);
+        }
+        info!(
+            "Resuming the download of {}: {} of {} chunks already downloaded.",
+            file.display(),
+            state.completed.count(),
+            chunks_number
+        );
+        state
+    }
+
+    /// Writes the state file, replacing the previous one atomically.
+    pub fn save(&self, file: &Path) -> Result<(), ZenohCdnError> {
+        let state_path = DownloadState::path_for(file);
+        let tmp_path = state_path.with_extension("state.tmp");
+        fs::write(&tmp_path, serde_json::to_string(self)?)?;
+        fs::rename(&tmp_path, &state_path)?;
+        Ok(())
+    }
+
+    /// Removes the state file, once the download is complete.
+    pub fn remove(file: &Path) -> Result<(), ZenohCdnError> {
+        let state_path = DownloadState::path_for(file);
+        if state_path.exists() {
+            fs::remove_file(&state_path)?;
+        }
+        Ok(())
+    }
+}
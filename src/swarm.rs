@@ -0,0 +1,234 @@
+//
+// Copyright (c) 2017, 2020 ADLINK Technology Inc.
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ADLINK zenoh team, <zenoh@adlink-labs.tech>
+//
+
+use crate::ChunkBitmap;
+use log::warn;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Consecutive failures after which a seeder is no longer asked for chunks.
+pub const MAX_SEEDER_FAILURES: usize = 3;
+/// Weight of the last answer in the latency estimate of a seeder.
+const LATENCY_WEIGHT: f64 = 0.3;
+
+/// Where a chunk is requested.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ChunkSource {
+    /// The seeder with the given id, at `<key>/seeders/<id>/<n>`.
+    Seeder(String),
+    /// Any seeder of the file, at `<key>/<n>`.
+    Any,
+}
+
+impl ChunkSource {
+    /// Returns the selector of the chunk number `chunk_num` of `key` on this source.
+    pub fn chunk_key(&self, key: &str, chunk_num: usize) -> String {
+        match self {
+            ChunkSource::Seeder(id) => format!("{}/seeders/{}/{}", key, id, chunk_num),
+            ChunkSource::Any => format!("{}/{}", key, chunk_num),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct SeederStats {
+    id: String,
+    in_flight: usize,
+    failures: usize,
+    latency: Option<f64>,
+    served: usize,
+    /// The chunks held by the seeder, if it published them.
+    availability: Option<ChunkBitmap>,
+}
+
+impl SeederStats {
+    fn new(id: String) -> SeederStats {
+        SeederStats {
+            id,
+            in_flight: 0,
+            failures: 0,
+            latency: None,
+            served: 0,
+            availability: None,
+        }
+    }
+
+    fn holds(&self, chunk_num: usize) -> bool {
+        match &self.availability {
+            Some(chunks) => chunks.contains(chunk_num),
+            None => true,
+        }
+    }
+}
+
+/// The seeders of a file found by a download, and how well each one answers.
+///
+/// Each chunk goes to the seeder expected to answer first, among the ones
+/// holding it according to their availability bitmap: the one with the
+/// lowest latency times the requests already waiting on it. Seeders that keep
+/// failing are left out, and without any seeder left the chunks are requested
+/// to any seeder of the file.
+#[derive(Debug, Default)]
+pub struct Swarm {
+    seeders: Mutex<Vec<SeederStats>>,
+}
+
+impl Swarm {
+    pub fn new<I: IntoIterator<Item = String>>(ids: I) -> Swarm {
+        let mut seeders: Vec<SeederStats> = Vec::new();
+        for id in ids {
+            if seeders.iter().all(|seeder| seeder.id != id) {
+                seeders.push(SeederStats::new(id));
+            }
+        }
+        Swarm {
+            seeders: Mutex::new(seeders),
+        }
+    }
+
+    /// Returns the ids of the seeders, in discovery order.
+    pub fn seeders(&self) -> Vec<String> {
+        let seeders = self.seeders.lock().unwrap();
+        seeders.iter().map(|seeder| seeder.id.clone()).collect()
+    }
+
+    /// Returns the number of chunks received from the seeder `id`.
+    pub fn served(&self, id: &str) -> usize {
+        let seeders = self.seeders.lock().unwrap();
+        seeders
+            .iter()
+            .find(|seeder| seeder.id == id)
+            .map_or(0, |seeder| seeder.served)
+    }
+
+    /// Returns the number of seeders holding the chunk number `chunk_num`,
+    /// without the ones that keep failing.
+    pub fn holders(&self, chunk_num: usize) -> usize {
+        let seeders = self.seeders.lock().unwrap();
+        seeders
+            .iter()
+            .filter(|seeder| seeder.failures < MAX_SEEDER_FAILURES)
+            .filter(|seeder| seeder.holds(chunk_num))
+            .count()
+    }
+
+    /// Sets the chunks held by the seeder `id`, adding the seeder if it is unknown.
+    pub fn set_availability(&self, id: &str, chunks: ChunkBitmap) {
+        let mut seeders = self.seeders.lock().unwrap();
+        match seeders.iter_mut().find(|seeder| seeder.id == id) {
+            Some(seeder) => seeder.availability = Some(chunks),
+            None => {
+                let mut seeder = SeederStats::new(id.to_string());
+                seeder.availability = Some(chunks);
+                seeders.push(seeder);
+            }
+        }
+    }
+
+    /// Chooses the source of the next request for the chunk number `chunk_num`,
+    /// counting it as in flight until the returned request is dropped.
+    /// The seeders in `excluded` and the ones without the chunk are skipped.
+    pub fn pick(&self, chunk_num: usize, excluded: &[String]) -> InFlight<'_> {
+        let mut seeders = self.seeders.lock().unwrap();
+        // A seeder not measured yet is expected to be as fast as the average one.
+        let known: Vec<f64> = seeders.iter().filter_map(|seeder| seeder.latency).collect();
+        let default_latency = if known.is_empty() {
+            1.0
+        } else {
+            known.iter().sum::<f64>() / known.len() as f64
+        };
+        let cost = |seeder: &SeederStats| {
+            seeder.latency.unwrap_or(default_latency) * (seeder.in_flight + 1) as f64
+        };
+        let best = seeders
+            .iter_mut()
+            .filter(|seeder| seeder.failures < MAX_SEEDER_FAILURES)
+            .filter(|seeder| !excluded.contains(&seeder.id))
+            .filter(|seeder| seeder.holds(chunk_num))
+            .min_by(|a, b| cost(a).partial_cmp(&cost(b)).unwrap());
+        let source = match best {
+            Some(seeder) => {
+                seeder.in_flight += 1;
+                ChunkSource::Seeder(seeder.id.clone())
+            }
+            None => ChunkSource::Any,
+        };
+        InFlight {
+            swarm: self,
+            source,
+        }
+    }
+
+    fn update<F: FnOnce(&mut SeederStats)>(&self, source: &ChunkSource, f: F) {
+        if let ChunkSource::Seeder(id) = source {
+            let mut seeders = self.seeders.lock().unwrap();
+            if let Some(seeder) = seeders.iter_mut().find(|seeder| &seeder.id == id) {
+                f(seeder);
+            }
+        }
+    }
+}
+
+/// A chunk request counted as in flight on its source, returned by `Swarm::pick`.
+///
+/// The request stops counting as in flight when it is dropped, whether its
+/// outcome has been recorded or the request has been abandoned, e.g. on a
+/// cancellation or a deadline.
+#[must_use]
+#[derive(Debug)]
+pub struct InFlight<'a> {
+    swarm: &'a Swarm,
+    source: ChunkSource,
+}
+
+impl InFlight<'_> {
+    /// Returns where the chunk is requested.
+    pub fn source(&self) -> &ChunkSource {
+        &self.source
+    }
+
+    /// Records a valid chunk received after `elapsed`.
+    pub fn succeeded(self, elapsed: Duration) {
+        self.swarm.update(&self.source, |seeder| {
+            seeder.failures = 0;
+            seeder.served += 1;
+            let elapsed = elapsed.as_secs_f64();
+            seeder.latency = Some(match seeder.latency {
+                Some(latency) => latency + LATENCY_WEIGHT * (elapsed - latency),
+                None => elapsed,
+            });
+        });
+    }
+
+    /// Records a request that timed out or had no valid reply.
+    pub fn failed(self) {
+        self.swarm.update(&self.source, |seeder| {
+            seeder.failures += 1;
+            if seeder.failures == MAX_SEEDER_FAILURES {
+                warn!(
+                    "Seeder {} failed {} times in a row. No more chunks requested to it.",
+                    seeder.id, seeder.failures
+                );
+            }
+        });
+    }
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.swarm.update(&self.source, |seeder| {
+            seeder.in_flight = seeder.in_flight.saturating_sub(1);
+        });
+    }
+}
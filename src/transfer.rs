@@ -0,0 +1,164 @@
+//
+// Copyright (c) 2017, 2020 ADLINK Technology Inc.
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ADLINK zenoh team, <zenoh@adlink-labs.tech>
+//
+
+use crate::{TransferReport, ZenohCdnError};
+use async_std::channel::{bounded, Receiver, Sender};
+use async_std::task::JoinHandle;
+use serde::Deserialize;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+const RUNNING: u8 = 0;
+const PAUSED: u8 = 1;
+const CANCELLED: u8 = 2;
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// What to do with the partially downloaded data when a download is cancelled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CleanupPolicy {
+    /// Keep the partial file and its state file, so that the download can be resumed.
+    KeepPartial,
+    /// Remove the partial file, its state file and its chunk files.
+    RemovePartial,
+}
+
+impl Default for CleanupPolicy {
+    fn default() -> Self {
+        CleanupPolicy::KeepPartial
+    }
+}
+
+/// What to do when the downloaded file already exists in the download folder.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExistingFilePolicy {
+    /// Replace the existing file once the download is verified.
+    Overwrite,
+    /// Keep the existing file, naming the downloaded one `<name> (<n>).<extension>`.
+    KeepBoth,
+    /// Do not download the file if the existing one matches the checksum of the manifest.
+    /// Otherwise it is replaced.
+    SkipIfSame,
+}
+
+impl Default for ExistingFilePolicy {
+    fn default() -> Self {
+        ExistingFilePolicy::Overwrite
+    }
+}
+
+/// Controls a running download. It can be cloned and shared between tasks.
+#[derive(Clone, Debug)]
+pub struct TransferControl {
+    state: Arc<AtomicU8>,
+    /// Closed on cancellation: every receiver then wakes up.
+    cancel_sender: Sender<()>,
+    cancel_receiver: Receiver<()>,
+}
+
+impl Default for TransferControl {
+    fn default() -> Self {
+        let (cancel_sender, cancel_receiver) = bounded(1);
+        TransferControl {
+            state: Arc::new(AtomicU8::new(RUNNING)),
+            cancel_sender,
+            cancel_receiver,
+        }
+    }
+}
+
+impl TransferControl {
+    /// Stops the download, interrupting the chunk requests in flight.
+    /// The download future then fails with `ZenohCdnError::Cancelled`.
+    pub fn cancel(&self) {
+        self.state.store(CANCELLED, Ordering::SeqCst);
+        self.cancel_sender.close();
+    }
+
+    /// Stops requesting new chunks until `resume` is called.
+    pub fn pause(&self) {
+        let _ = self
+            .state
+            .compare_exchange(RUNNING, PAUSED, Ordering::SeqCst, Ordering::SeqCst);
+    }
+
+    /// Resumes a paused download.
+    pub fn resume(&self) {
+        let _ = self
+            .state
+            .compare_exchange(PAUSED, RUNNING, Ordering::SeqCst, Ordering::SeqCst);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.state.load(Ordering::SeqCst) == PAUSED
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.state.load(Ordering::SeqCst) == CANCELLED
+    }
+
+    /// Waits until the download is cancelled.
+    pub async fn cancelled(&self) {
+        // Nothing is ever sent: the receiver only wakes up once the channel is closed.
+        let _ = self.cancel_receiver.recv().await;
+    }
+
+    /// Waits until the download is resumed or cancelled.
+    pub(crate) async fn wait_while_paused(&self) {
+        while self.is_paused() {
+            async_std::task::sleep(PAUSE_POLL_INTERVAL).await;
+        }
+    }
+}
+
+/// A download running in the background, returned by `ZenohCdn::download_with_handle`.
+pub struct TransferHandle {
+    control: TransferControl,
+    task: JoinHandle<Result<TransferReport, ZenohCdnError>>,
+}
+
+impl TransferHandle {
+    pub(crate) fn new(
+        control: TransferControl,
+        task: JoinHandle<Result<TransferReport, ZenohCdnError>>,
+    ) -> TransferHandle {
+        TransferHandle { control, task }
+    }
+
+    /// Returns a control of the download that can be moved to another task.
+    pub fn control(&self) -> TransferControl {
+        self.control.clone()
+    }
+
+    /// Cancels the download, cleaning up according to the `CleanupPolicy` of the download.
+    pub fn cancel(&self) {
+        self.control.cancel();
+    }
+
+    /// Pauses the download.
+    pub fn pause(&self) {
+        self.control.pause();
+    }
+
+    /// Resumes the download.
+    pub fn resume(&self) {
+        self.control.resume();
+    }
+
+    /// Waits for the end of the download and returns its report.
+    pub async fn join(self) -> Result<TransferReport, ZenohCdnError> {
+        self.task.await
+    }
+}
@@ -12,149 +12,404 @@
 //   ADLINK zenoh team, <zenoh@adlink-labs.tech>
 //
 
-use crate::{EVALApiArgs, PUTApiArgs};
+use crate::manifest::chunk_digest;
+use crate::{
+    DownloadState, EVALApiArgs, ExistingFilePolicy, FileManifest, HashAlgorithm, PUTApiArgs,
+    ZenohCdnError,
+};
 use log::{error, info, warn};
-use memmap::MmapOptions;
+use memmap::{Mmap, MmapMut, MmapOptions};
+use std::fs;
 use std::fs::create_dir_all;
 use std::fs::File;
-use std::path::Path;
+use std::fs::OpenOptions;
+use std::path::{Component, Path, PathBuf};
 use std::str;
+use std::time::{Duration, Instant};
 use std::{
-    error::Error,
     io::{Read, Write},
     u64,
 };
-use std::{fs, io::ErrorKind};
-use std::{
-    fs::OpenOptions,
-    io::{Seek, SeekFrom},
-};
-
-const ROOT_FOLDER: &str = "/tmp";
 
-pub fn check_put_args(path: &str, value: &str, args: PUTApiArgs) -> Result<usize, Box<dyn Error>> {
+pub fn check_put_args(path: &str, value: &str, args: PUTApiArgs) -> Result<usize, ZenohCdnError> {
     if path.is_empty() {
-        return Err(std::io::Error::new(ErrorKind::InvalidInput, "Path is empty.").into());
+        return Err(ZenohCdnError::InvalidArgument("Path is empty.".into()));
     }
     if value.is_empty() {
-        return Err(std::io::Error::new(ErrorKind::InvalidInput, "Value is empty.").into());
+        return Err(ZenohCdnError::InvalidArgument("Value is empty.".into()));
     }
     if args.chunk_size < 1000 {
-        return Err(
-            std::io::Error::new(ErrorKind::InvalidInput, "Wrong chunk size: too small.").into(),
-        );
+        return Err(ZenohCdnError::InvalidArgument(
+            "Wrong chunk size: too small.".into(),
+        ));
     }
     Ok(args.chunk_size)
 }
 
-pub fn check_get_args(selector: String) -> Result<(), Box<dyn Error>> {
+/// Maximum length, in bytes, of a filename on the usual filesystems.
+const NAME_MAX: usize = 255;
+
+/// Maximum length, in bytes, of the name of a downloaded file, leaving room for
+/// the longest name derived from it: its temporary state file `.<name>.state.tmp`.
+const MAX_FILENAME_LEN: usize = NAME_MAX - ".".len() - ".state.tmp".len();
+
+pub fn check_get_args(selector: String) -> Result<(), ZenohCdnError> {
     if selector.is_empty() {
-        return Err(std::io::Error::new(ErrorKind::InvalidInput, "Selector is empty.").into());
+        return Err(ZenohCdnError::InvalidArgument("Selector is empty.".into()));
     }
+    filename_from_key(&selector)?;
     Ok(())
 }
 
-pub fn check_eval_args(path_str: String, args: EVALApiArgs) -> Result<usize, Box<dyn Error>> {
+/// Returns true for the characters a name taken from the network must not contain:
+/// control and invisible formatting characters, path separators and their lookalikes,
+/// drive separators and wildcards.
+fn is_forbidden_char(c: char) -> bool {
+    c.is_control()
+        || matches!(
+            c,
+            '/' | '\\'
+                | ':'
+                | '*'
+                | '?'
+                | '"'
+                | '<'
+                | '>'
+                | '|'
+                | '\u{200b}'..='\u{200f}'
+                | '\u{202a}'..='\u{202e}'
+                | '\u{2066}'..='\u{2069}'
+                | '\u{feff}'
+                | '\u{2044}'
+                | '\u{2215}'
+                | '\u{29f8}'
+                | '\u{ff0f}'
+                | '\u{ff3c}'
+        )
+}
+
+/// Returns the name of the downloaded file of `key`: its last segment.
+///
+/// The name comes from the network, so it is validated rather than rewritten:
+/// it must be a single plain file name that cannot point outside the download
+/// folders, nor be mistaken for the hidden files of a download.
+pub fn filename_from_key(key: &str) -> Result<String, ZenohCdnError> {
+    let name = key.rsplit('/').next().unwrap_or_default();
+    let invalid = |reason: &str| {
+        error!("Invalid filename {:?} in {:?}: {}.", name, key, reason);
+        Err(ZenohCdnError::InvalidArgument(format!(
+            "Invalid filename {:?}: {}.",
+            name, reason
+        )))
+    };
+    if name.is_empty() {
+        return invalid("the key ends with a separator");
+    }
+    if name.len() > MAX_FILENAME_LEN {
+        return invalid("too long");
+    }
+    if name.starts_with('.') {
+        return invalid("relative or hidden name");
+    }
+    if name.ends_with('.') || name.ends_with(' ') {
+        return invalid("trailing dot or space");
+    }
+    if name.chars().any(is_forbidden_char) {
+        return invalid("forbidden character");
+    }
+    // Percent-encoded separators and dots could be decoded by another tool.
+    let lowercase = name.to_lowercase();
+    if ["%2e", "%2f", "%5c", "%00"]
+        .iter()
+        .any(|encoded| lowercase.contains(encoded))
+    {
+        return invalid("encoded separator");
+    }
+    let mut components = Path::new(name).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) => Ok(name.to_string()),
+        _ => invalid("not a plain file name"),
+    }
+}
+
+/// Checks the key of a file requested to an eval, as taken from the selector
+/// of the request: it must be a concrete key, without wildcards nor relative segments.
+pub fn check_requested_key(key: &str) -> Result<(), ZenohCdnError> {
+    let invalid = key.split('/').skip(1).any(|segment| {
+        segment.is_empty()
+            || segment == "."
+            || segment == ".."
+            || segment.chars().any(|c| c.is_control() || c == '*')
+    });
+    if !key.starts_with('/') || invalid {
+        return Err(ZenohCdnError::InvalidArgument(format!(
+            "Invalid requested key {:?}.",
+            key
+        )));
+    }
+    Ok(())
+}
+
+pub fn check_eval_args(path_str: String, args: EVALApiArgs) -> Result<usize, ZenohCdnError> {
     if path_str.is_empty() {
-        return Err(std::io::Error::new(ErrorKind::InvalidInput, "Path is empty.").into());
+        return Err(ZenohCdnError::InvalidArgument("Path is empty.".into()));
     }
     if 0 == args.chunk_size {
-        return Err(std::io::Error::new(ErrorKind::InvalidInput, "Chunk size is zero.").into());
+        return Err(ZenohCdnError::InvalidArgument("Chunk size is zero.".into()));
     }
     Ok(args.chunk_size)
 }
 
-pub fn get_bytes_from_file(filename: &str, chunk_number: usize, chunk_size: usize) -> Vec<u8> {
-    let full_filename = format!("{}/{}", ROOT_FOLDER, filename);
-    info!(
-        "Getting the file {}, chunk number {}.",
-        full_filename, chunk_number
-    );
-    let mut f = File::open(&full_filename).expect("No file found");
-    let metadata = fs::metadata(&full_filename).expect("Unable to read metadata");
-    let file_size = metadata.len() as usize;
+/// Returns the path of the copy of the file shared on `key`.
+///
+/// The whole key is escaped into a single filename, so that files shared
+/// with the same name on different keys never overwrite each other.
+/// A key too long to fit in a filename is replaced by its digest, in a name
+/// that no escaped key can take.
+pub fn staged_path(staging_folder: &Path, key: &str) -> PathBuf {
+    let mut name = String::with_capacity(key.len());
+    for (i, c) in key.trim_start_matches('/').chars().enumerate() {
+        match c {
+            '/' => name.push_str("%2F"),
+            '%' => name.push_str("%25"),
+            // Neither hidden nor a "." or ".." entry.
+            '.' if i == 0 => name.push_str("%2E"),
+            c if c.is_control() => name.push_str(&format!("%{:02X}", c as u32)),
+            c => name.push(c),
+        }
+    }
+    if name.len() > NAME_MAX {
+        // An escaped key never has a '%' followed by anything but two hex digits.
+        name = format!(
+            "%key-{}",
+            chunk_digest(HashAlgorithm::default(), key.as_bytes())
+        );
+    }
+    staging_folder.join(name)
+}
 
-    let offset: usize = (chunk_number - 1) * chunk_size;
-    let real_offset = f.seek(SeekFrom::Start(offset as u64));
-    info!(
-        "The offset I'd like is {} and the real offset is {:?}.",
-        offset, real_offset
-    );
+/// Copies `source` to `destination` through a temporary file renamed over it.
+///
+/// A file already at `destination` may still be mapped by the evals serving it:
+/// it is replaced by a new file, never rewritten in place.
+pub fn copy_replacing(source: &Path, destination: &Path) -> std::io::Result<()> {
+    let folder = destination.parent().unwrap_or_else(|| Path::new("."));
+    let temporary = folder.join(format!(".{:016x}.tmp", rand::random::<u64>()));
+    let copied = fs::copy(source, &temporary).and_then(|_| fs::rename(&temporary, destination));
+    if copied.is_err() {
+        let _ = fs::remove_file(&temporary);
+    }
+    copied.map(|_| ())
+}
 
-    let missing_bytes = file_size - offset;
-    let buffer_len: usize = missing_bytes.min(chunk_size);
+/// Maps a shared file in memory to serve its chunks. An empty file cannot be mapped.
+pub fn map_shared_file(file: &Path) -> Result<Mmap, ZenohCdnError> {
+    info!("Mapping the file {}.", file.display());
+    let f = File::open(file)?;
+    if f.metadata()?.len() == 0 {
+        // An empty file cannot be mapped, and it has no chunk to serve.
+        return Err(ZenohCdnError::InvalidRange(format!(
+            "The file {} is empty.",
+            file.display()
+        )));
+    }
+    let data = unsafe { MmapOptions::new().map(&f)? };
+    Ok(data)
+}
+
+/// Returns the chunk number `chunk_number` of a mapped file, shorter if it is the last one.
+pub fn get_chunk_from_mmap(
+    data: &Mmap,
+    chunk_number: usize,
+    chunk_size: usize,
+) -> Result<&[u8], ZenohCdnError> {
+    let file_size = data.len();
+    let offset: usize = chunk_number.saturating_sub(1).saturating_mul(chunk_size);
+    if chunk_number == 0 || offset >= file_size {
+        return Err(ZenohCdnError::InvalidRange(format!(
+            "Chunk {} is out of a file of {} bytes.",
+            chunk_number, file_size
+        )));
+    }
+    let end = file_size.min(offset + chunk_size);
     info!(
-        "File size {}, missing_bytes {}. I create a vector of {} bytes.",
-        file_size, missing_bytes, buffer_len
+        "Getting chunk number {}, from position {} to position {}.",
+        chunk_number, offset, end
     );
-    let mut buffer = vec![0; buffer_len];
-    f.read_exact(&mut buffer)
-        .expect("Error during file reading [read_exact].");
-    buffer
+    Ok(&data[offset..end])
+}
+
+/// Returns `timeout`, shortened so that it ends by `deadline` if any.
+pub fn within_deadline(timeout: Duration, deadline: Option<Instant>) -> Duration {
+    match deadline {
+        Some(deadline) => timeout.min(deadline.saturating_duration_since(Instant::now())),
+        None => timeout,
+    }
+}
+
+/// Reads a file while computing the digest of each of its chunks.
+struct ChunkDigester<R> {
+    inner: R,
+    algorithm: HashAlgorithm,
+    chunk_size: usize,
+    chunk: Vec<u8>,
+    digests: Vec<String>,
+    size: usize,
+    error: Option<std::io::Error>,
+}
+
+impl<R: Read> Read for ChunkDigester<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = match self.inner.read(buf) {
+            Ok(n) => n,
+            Err(e) => {
+                // The hasher cannot report an error: the read ends, and the error is kept.
+                self.error = Some(e);
+                return Ok(0);
+            }
+        };
+        self.size += n;
+        let mut bytes = &buf[..n];
+        while !bytes.is_empty() {
+            let taken = bytes.len().min(self.chunk_size - self.chunk.len());
+            self.chunk.extend_from_slice(&bytes[..taken]);
+            bytes = &bytes[taken..];
+            if self.chunk.len() == self.chunk_size {
+                self.digests.push(chunk_digest(self.algorithm, &self.chunk));
+                self.chunk.clear();
+            }
+        }
+        Ok(n)
+    }
+}
+
+/// Computes the size, the checksum and the chunk digests of a file, reading it once.
+/// An empty file has a single empty chunk.
+pub fn get_file_digests(
+    file: &Path,
+    chunk_size: usize,
+    algorithm: HashAlgorithm,
+) -> Result<(usize, String, Vec<String>), ZenohCdnError> {
+    let mut reader = ChunkDigester {
+        inner: std::io::BufReader::new(File::open(file)?),
+        algorithm,
+        chunk_size,
+        chunk: Vec::with_capacity(chunk_size),
+        digests: Vec::new(),
+        size: 0,
+        error: None,
+    };
+    let checksum = checksums::hash_reader(&mut reader, algorithm.algorithm());
+    if let Some(e) = reader.error {
+        return Err(e.into());
+    }
+    if !reader.chunk.is_empty() || reader.digests.is_empty() {
+        reader.digests.push(chunk_digest(algorithm, &reader.chunk));
+    }
+    info!("Computed {} chunk digests.", reader.digests.len());
+    Ok((reader.size, checksum, reader.digests))
 }
 
-#[allow(clippy::type_complexity)]
 pub fn get_metadata_info(
-    metadata: String,
+    metadata: &str,
     old_selector: String,
-) -> Result<(usize, String, usize, usize, String), Box<dyn Error>> {
+) -> Result<(FileManifest, String), ZenohCdnError> {
     info!("\nMetadata {:?}", metadata);
 
-    let metadata_split: Vec<_> = metadata.split(", ").collect();
-    let metadata_size: Vec<_> = metadata_split[0].split(": ").collect();
-    let metadata_checksum: Vec<_> = metadata_split[1].split(": ").collect();
-    let metadata_chunks_number: Vec<_> = metadata_split[2].split(": ").collect();
-    let metadata_chunk_size: Vec<_> = metadata_split[3].split(": ").collect();
-    let selector_split: Vec<_> = old_selector.split('/').collect();
-
-    let size: usize = match metadata_size[1].parse::<usize>() {
-        Ok(s) => {
-            info!("File size: {}", s);
-            s
-        }
+    let manifest = match FileManifest::from_json(metadata) {
+        Ok(manifest) => manifest,
         Err(e) => {
-            error!("Cannot find the file size.");
-            return Err(e.into());
+            error!("Cannot read the file manifest: {}", e);
+            return Err(e);
         }
     };
+    info!("File size: {}", manifest.size);
+    info!("Checksum: {}", manifest.digest);
+    info!("Chunks number: {}", manifest.chunks_number);
+    info!("Chunks size: {}", manifest.chunk_size);
 
-    let checksum: String = match metadata_checksum[1].parse::<String>() {
-        Ok(s) => {
-            info!("File size: {}", s);
-            s
-        }
-        Err(e) => {
-            error!("Cannot find the file size.");
-            return Err(e.into());
+    if manifest.has_chunk_digests() {
+        if !manifest.check_merkle_root() {
+            error!("The chunk digests do not match the Merkle root.");
+            return Err(ZenohCdnError::ManifestMalformed(
+                "The chunk digests do not match the Merkle root.".into(),
+            ));
         }
-    };
+        info!("Merkle root: {}", manifest.merkle_root);
+    } else {
+        warn!("The manifest has no chunk digests: chunks will not be verified on arrival.");
+    }
 
-    let chunks_number: usize = match metadata_chunks_number[1].parse::<usize>() {
-        Ok(s) => {
-            info!("Chunks number: {}", s);
-            s
-        }
-        Err(e) => {
-            error!("Cannot find the number of chunks.");
-            return Err(e.into());
-        }
-    };
+    if manifest.last_chunk_size != 0
+        && manifest.last_chunk_size != manifest.chunk_len(manifest.chunks_number)
+    {
+        error!("The last chunk size does not match the file size.");
+        return Err(ZenohCdnError::ManifestMalformed(
+            "The last chunk size does not match the file size.".into(),
+        ));
+    }
+
+    let filename = filename_from_key(&old_selector)?;
+    info!("Filename: {}\n", filename);
+
+    Ok((manifest, filename))
+}
 
-    let chunk_size: usize = match metadata_chunk_size[1].parse::<usize>() {
-        Ok(s) => {
-            info!("Chunks size: {}", s);
-            s
+/// Checks that each folder has room for the bytes about to be written in it.
+/// Folders on the same file system share its free space.
+pub fn check_free_space(folders: &[(&Path, u64)]) -> Result<(), ZenohCdnError> {
+    let mut file_systems: Vec<(PathBuf, u64, u64)> = Vec::new();
+    for (folder, bytes) in folders {
+        let existing = existing_ancestor(folder);
+        let available = fs2::available_space(&existing)?;
+        match file_systems
+            .iter_mut()
+            .find(|(other, ..)| same_file_system(other, &existing))
+        {
+            Some((_, needed, _)) => *needed += bytes,
+            None => file_systems.push((existing, *bytes, available)),
         }
-        Err(e) => {
-            error!("Cannot find the size of the chunks.");
-            return Err(e.into());
+    }
+    for (path, needed, available) in file_systems {
+        info!(
+            "{} bytes to write in {}, {} available.",
+            needed,
+            path.display(),
+            available
+        );
+        if needed > available {
+            error!("Not enough free space in {}.", path.display());
+            return Err(ZenohCdnError::InsufficientSpace {
+                path,
+                needed,
+                available,
+            });
         }
-    };
+    }
+    Ok(())
+}
 
-    let filename = String::from(selector_split[selector_split.len() - 1]);
-    info!("Filename: {}\n", filename);
+/// Returns the folder itself or, if it is not created yet, its closest existing parent.
+fn existing_ancestor(folder: &Path) -> PathBuf {
+    folder
+        .ancestors()
+        .find(|ancestor| ancestor.is_dir())
+        .unwrap_or_else(|| Path::new("."))
+        .to_path_buf()
+}
 
-    Ok((size, checksum, chunks_number, chunk_size, filename))
+#[cfg(unix)]
+fn same_file_system(a: &Path, b: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (fs::metadata(a), fs::metadata(b)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev(),
+        _ => a == b,
+    }
+}
+
+#[cfg(not(unix))]
+fn same_file_system(a: &Path, b: &Path) -> bool {
+    a == b
 }
 
 pub fn get_chunks_interval(
@@ -164,8 +419,8 @@ pub fn get_chunks_interval(
     index_end: usize,
     chunk_index_start: usize,
     chunk_index_end: usize,
-) -> Result<(usize, usize), Box<dyn Error>> {
-    let mut chunk_start: usize = 0;
+) -> Result<(usize, usize), ZenohCdnError> {
+    let mut chunk_start: usize = 1;
     let mut chunk_end: usize = chunks_number;
     info!(
         "Indeces: bytes start-{}, bytes end-{}, chunk_start-{}, chunk_end-{}",
@@ -173,9 +428,13 @@ pub fn get_chunks_interval(
     );
 
     if index_start > index_end {
-        return Err("Wrong bytes interval specified.".into());
+        return Err(ZenohCdnError::InvalidRange(
+            "Wrong bytes interval specified.".into(),
+        ));
     } else if chunk_index_start > chunk_index_end {
-        return Err("Wrong chunks interval specified.".into());
+        return Err(ZenohCdnError::InvalidRange(
+            "Wrong chunks interval specified.".into(),
+        ));
     } else if index_end != 0 {
         chunk_start = index_start / chunk_size + 1;
         let chunk_end_raw = index_end / chunk_size + 1;
@@ -185,7 +444,7 @@ pub fn get_chunks_interval(
             chunk_start, chunk_end
         );
     } else if chunk_index_end != 0 {
-        chunk_start = chunk_index_start;
+        chunk_start = chunk_index_start.max(1);
         chunk_end = chunk_index_end.min(chunks_number);
         info!(
             "Chunks decision: chunk start {}, chunk end {}",
@@ -194,16 +453,21 @@ pub fn get_chunks_interval(
     }
 
     info!("Chunk_start {}, chunk_end {}", chunk_start, chunk_end);
+    if chunk_start > chunk_end {
+        return Err(ZenohCdnError::InvalidRange(
+            "The interval specified is outside the file.".into(),
+        ));
+    }
 
     Ok((chunk_start, chunk_end))
 }
 
 pub fn create_mmap_file(
     path: String,
-    root_folder_final: &str,
+    root_folder_final: &Path,
     size: u64,
-) -> Result<File, Box<dyn Error>> {
-    let mut f = match OpenOptions::new()
+) -> Result<File, ZenohCdnError> {
+    let f = match OpenOptions::new()
         .read(true)
         .write(true)
         .create(true)
@@ -212,9 +476,12 @@ pub fn create_mmap_file(
         Ok(f) => f,
         Err(_) => {
             warn!("Cannot create the file {}.", path);
-            warn!("Checking if the folder {} exists.", root_folder_final);
-            create_dir_all(&root_folder_final)?;
-            info!("Created the folder {}.", root_folder_final);
+            warn!(
+                "Checking if the folder {} exists.",
+                root_folder_final.display()
+            );
+            create_dir_all(root_folder_final)?;
+            info!("Created the folder {}.", root_folder_final.display());
             match OpenOptions::new()
                 .read(true)
                 .write(true)
@@ -230,56 +497,174 @@ pub fn create_mmap_file(
         }
     };
 
-    // Allocate space in the file first
-    f.seek(SeekFrom::Start(size - 1))?;
-    f.write_all(&[0])?;
-    f.seek(SeekFrom::Start(0))?;
+    // Allocate space in the file first, keeping what a previous download wrote
+    f.set_len(size)?;
     Ok(f)
 }
 
-pub fn write_mmap_file(f: &File, src: Vec<u8>, chunk_num: usize, chunk_size: usize) {
-    let mut data = unsafe {
-        MmapOptions::new()
-            .map_mut(f)
-            .expect("Could not access data from memory mapped file")
-    };
+pub fn map_mmap_file(f: &File) -> Result<MmapMut, ZenohCdnError> {
+    if f.metadata()?.len() == 0 {
+        // An empty file cannot be mapped.
+        return Err(ZenohCdnError::InvalidRange("The file is empty.".into()));
+    }
+    let data = unsafe { MmapOptions::new().map_mut(f)? };
+    Ok(data)
+}
+
+pub fn write_mmap_file(
+    data: &mut MmapMut,
+    src: &[u8],
+    chunk_num: usize,
+    chunk_size: usize,
+) -> Result<(), ZenohCdnError> {
     let initial_position: usize = (chunk_num - 1) * chunk_size;
     let final_position: usize = initial_position + src.len();
     info!(
         "Write from position {} to position {}.",
         initial_position, final_position
     );
-    data[initial_position..final_position].copy_from_slice(&src);
+    if final_position > data.len() {
+        error!(
+            "Chunk {} ends at {}, beyond the file size {}.",
+            chunk_num,
+            final_position,
+            data.len()
+        );
+        return Err(ZenohCdnError::InvalidRange(format!(
+            "Chunk {} does not fit in the file.",
+            chunk_num
+        )));
+    }
+    data[initial_position..final_position].copy_from_slice(src);
+    Ok(())
 }
 
 pub fn write_file(
-    root_folder_chunks: &str,
+    root_folder_chunks: &Path,
     all_bytes: Vec<u8>,
-    filename: String,
-) -> Result<(), Box<dyn Error>> {
-    let mut f = match File::create(filename.clone()) {
+    filename: PathBuf,
+) -> Result<(), ZenohCdnError> {
+    let mut f = match File::create(&filename) {
         Ok(f) => f,
         Err(_) => {
-            warn!("Cannot create the file {}.", filename);
-            warn!("Checking if the folder {} exists...", root_folder_chunks);
+            warn!("Cannot create the file {}.", filename.display());
+            warn!(
+                "Checking if the folder {} exists...",
+                root_folder_chunks.display()
+            );
             create_dir_all(root_folder_chunks)?;
-            warn!("Created the folder {}.", root_folder_chunks);
-            match File::create(filename.clone()) {
+            warn!("Created the folder {}.", root_folder_chunks.display());
+            match File::create(&filename) {
                 Ok(f) => f,
                 Err(e) => {
-                    error!("Cannot create, again, the file {}.", filename);
+                    error!("Cannot create, again, the file {}.", filename.display());
                     return Err(e.into());
                 }
             }
         }
     };
-    f.write_all(&all_bytes).expect("Unable to write data");
+    f.write_all(&all_bytes)?;
     info!("Created file: {:?}", filename);
     Ok(())
 }
 
-pub fn check_checksum(checksum_old: String, file: &str) -> bool {
-    let checksum_new = checksums::hash_file(Path::new(file), checksums::Algorithm::SHA2256);
+/// Returns the temporary path where a file is downloaded until it is verified:
+/// a hidden file next to the final one.
+pub fn partial_path(path: &Path) -> PathBuf {
+    let filename = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}.part", filename))
+}
+
+/// Creates an empty file at `path`, or else at the first free `<stem> (<n>).<extension>`,
+/// and returns its path. The file is created only if the name is free, so that two
+/// downloads cannot take the same name.
+pub fn reserve_available_path(path: &Path) -> Result<PathBuf, ZenohCdnError> {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let extension = path
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+    let mut candidate = path.to_path_buf();
+    let mut n: usize = 0;
+    loop {
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&candidate)
+        {
+            Ok(_) => return Ok(candidate),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                n += 1;
+                candidate = path.with_file_name(format!("{} ({}){}", stem, n, extension));
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+/// Moves a verified download from its temporary path to its final path, returning it.
+///
+/// With `ExistingFilePolicy::KeepBoth`, the final path is reserved first, so that
+/// no file is ever replaced. Otherwise the file at `path`, if any, is replaced.
+/// The file is synced to the disk first, so that the final path holds either
+/// the previous file or the whole new one, even after a crash.
+pub fn finalize_file(
+    file: &File,
+    partial: &Path,
+    path: &Path,
+    policy: ExistingFilePolicy,
+) -> Result<PathBuf, ZenohCdnError> {
+    file.sync_all()?;
+    let final_path = match policy {
+        ExistingFilePolicy::KeepBoth => reserve_available_path(path)?,
+        ExistingFilePolicy::Overwrite | ExistingFilePolicy::SkipIfSame => path.to_path_buf(),
+    };
+    if let Err(e) = fs::rename(partial, &final_path) {
+        if policy == ExistingFilePolicy::KeepBoth {
+            let _ = fs::remove_file(&final_path);
+        }
+        return Err(e.into());
+    }
+    // Persist the rename itself. Not every platform can sync a folder.
+    if let Some(folder) = final_path.parent() {
+        if let Ok(folder) = File::open(folder) {
+            let _ = folder.sync_all();
+        }
+    }
+    info!("Moved {} to {}.", partial.display(), final_path.display());
+    Ok(final_path)
+}
+
+/// Removes the partial file, the state and the chunk files of the download of `path`.
+pub fn remove_partial_download(
+    path: &Path,
+    root_folder_chunks: &Path,
+    filename: &str,
+    chunks_number: usize,
+) -> Result<(), ZenohCdnError> {
+    let partial = partial_path(path);
+    info!("Removing the partial download {}.", partial.display());
+    DownloadState::remove(path)?;
+    if partial.exists() {
+        fs::remove_file(&partial)?;
+    }
+    for chunk_num in 1..=chunks_number {
+        let full_filename = root_folder_chunks.join(format!("{}_{}", filename, chunk_num));
+        if full_filename.exists() {
+            fs::remove_file(&full_filename)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn check_checksum(checksum_old: String, algorithm: HashAlgorithm, file: &str) -> bool {
+    let checksum_new = checksums::hash_file(Path::new(file), algorithm.algorithm());
     info!("\nChecksum old: {}", checksum_old);
     info!("Checksum new: {}", checksum_new);
     checksum_old.eq(&checksum_new)
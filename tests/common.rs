@@ -2,7 +2,11 @@
 ///
 extern crate fragmentation_e2e;
 use core::default::Default;
-use fragmentation_e2e::{EVALApiArgs, GETApiChunksArgs, GETApiFoldersArgs, PUTApiArgs, ZenohCdn};
+use fragmentation_e2e::{
+    EVALApiArgs, GETApiChunksArgs, GETApiFoldersArgs, PUTApiArgs, SeedHandle, ZenohCdn,
+    ZenohCdnError,
+};
+use std::path::PathBuf;
 use zenoh::Properties;
 
 pub fn setup_put(
@@ -82,11 +86,7 @@ pub async fn call_put(
         }
         Err(e) => {
             println!("Error during the Put: {:?}.", e);
-            if let Some(ierr) = e.downcast_ref::<std::io::Error>() {
-                Err(ierr.kind())
-            } else {
-                Err(std::io::ErrorKind::Other)
-            }
+            Err(std::io::Error::from(e).kind())
         }
     }
 }
@@ -105,14 +105,15 @@ pub async fn call_get(
     let mut zenohcdn = ZenohCdn::new_session(config).await.unwrap();
 
     zenohcdn.set_download_folders(GETApiFoldersArgs {
-        root_folder_final,
-        root_folder_chunks,
+        root_folder_final: root_folder_final.into(),
+        root_folder_chunks: root_folder_chunks.into(),
     });
     zenohcdn.set_download_bytes_args(GETApiChunksArgs {
         index_start,
         index_end,
         chunk_index_start,
         chunk_index_end,
+        ..Default::default()
     });
 
     match zenohcdn.download(selector, "").await {
@@ -122,11 +123,7 @@ pub async fn call_get(
         }
         Err(e) => {
             println!("Error during the Get: {:?}.", e);
-            if let Some(ierr) = e.downcast_ref::<std::io::Error>() {
-                Err(ierr.kind())
-            } else {
-                Err(std::io::ErrorKind::Other)
-            }
+            Err(std::io::Error::from(e).kind())
         }
     }
 }
@@ -148,11 +145,50 @@ pub async fn call_eval(
         }
         Err(e) => {
             println!("Error during the EVAL: {:?}.", e);
-            if let Some(ierr) = e.downcast_ref::<std::io::Error>() {
-                Err(ierr.kind())
-            } else {
-                Err(std::io::ErrorKind::Other)
-            }
+            Err(std::io::Error::from(e).kind())
         }
     }
 }
+
+/// Returns the path and the content of the image shared by the end-to-end tests.
+pub fn test_file() -> (String, Vec<u8>) {
+    let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    d.push("tests/zenoh.png");
+    let content = std::fs::read(&d).unwrap();
+    (format!("{}", d.display()), content)
+}
+
+/// Shares the test image on `key` from a new peer, returning its content.
+pub async fn share_test_file(key: &str, chunk_size: usize) -> (ZenohCdn, SeedHandle, Vec<u8>) {
+    let (file, content) = test_file();
+    let (config, path, value, chunk_size) = setup_put("peer", key, &file, chunk_size);
+    let mut zenohcdn = ZenohCdn::new_session(config).await.unwrap();
+    zenohcdn.set_upload_args(PUTApiArgs { chunk_size });
+    let seed = zenohcdn.upload(path, value).await.unwrap();
+    (zenohcdn, seed, content)
+}
+
+/// Returns the folder `name` of the tests, in the temporary directory.
+pub fn temp_folder(name: &str) -> PathBuf {
+    std::env::temp_dir().join("cdn-tests").join(name)
+}
+
+/// Creates a new peer downloading into the test folder `name`, which is emptied first.
+pub async fn downloader(name: &str) -> ZenohCdn {
+    let folder = temp_folder(name);
+    let _ = std::fs::remove_dir_all(&folder);
+    let (config, ..) = setup_get("peer", "", 0, 0, 0, 0);
+    let mut zenohcdn = ZenohCdn::new_session(config).await.unwrap();
+    zenohcdn.set_download_folders(GETApiFoldersArgs {
+        root_folder_final: folder.join("final"),
+        root_folder_chunks: folder.join("chunks"),
+    });
+    zenohcdn
+}
+
+pub fn kind_of<T>(result: Result<T, ZenohCdnError>) -> Result<T, std::io::ErrorKind> {
+    result.map_err(|e| {
+        println!("Error: {:?}.", e);
+        std::io::Error::from(e).kind()
+    })
+}